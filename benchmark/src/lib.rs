@@ -9,7 +9,6 @@ use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::prelude::FileExt;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Barrier, Mutex};
 use std::time::{Duration, Instant};
 
@@ -164,22 +163,21 @@ pub fn populate_store(store: &Arc<FasterKv>, keys: &Arc<Vec<u64>>, num_threads:
                     .unwrap();
             }
 
-            let _session = store.start_session();
+            let session = store.session();
             let mut chunk_idx = idx.fetch_add(K_CHUNK_SIZE, Ordering::SeqCst);
             while chunk_idx < K_INIT_COUNT {
                 for i in chunk_idx..(chunk_idx + K_CHUNK_SIZE) {
                     if i % K_REFRESH_INTERVAL == 0 {
-                        store.refresh();
+                        session.refresh();
                         if i % K_COMPLETE_PENDING_INTERVAL == 0 {
-                            store.complete_pending(false);
+                            session.complete_pending(false).unwrap();
                         }
                     }
-                    store.upsert(&*keys.get(i as usize).unwrap(), &42, i as u64);
+                    session.upsert(&*keys.get(i as usize).unwrap(), &42).unwrap();
                 }
                 chunk_idx = idx.fetch_add(K_CHUNK_SIZE, Ordering::SeqCst);
             }
-            store.complete_pending(true);
-            store.stop_session();
+            session.complete_pending(true).unwrap();
         }));
     }
     for t in threads {
@@ -225,7 +223,7 @@ pub fn run_benchmark<F: Fn(usize) -> Operation + Send + Copy + 'static>(
                     let mut upserts = 0;
                     let mut rmws = 0;
 
-                    let _session = store.start_session();
+                    let session = store.session();
 
                     barrier.wait();
                     let start = Instant::now();
@@ -239,31 +237,30 @@ pub fn run_benchmark<F: Fn(usize) -> Operation + Send + Copy + 'static>(
                         }
                         for i in chunk_idx..(chunk_idx + K_CHUNK_SIZE) {
                             if i % K_REFRESH_INTERVAL == 0 {
-                                store.refresh();
+                                session.refresh();
                                 if i % K_COMPLETE_PENDING_INTERVAL == 0 {
-                                    store.complete_pending(false);
+                                    session.complete_pending(false).unwrap();
                                 }
                             }
                             match op_allocator(i) {
                                 Operation::Read => {
-                                    let (_, _): (u8, Receiver<i32>) =
-                                        store.read(&*keys.get(i).unwrap(), 1);
+                                    session.read::<u64, i32>(&*keys.get(i).unwrap()).unwrap();
                                     reads += 1;
                                 }
                                 Operation::Upsert => {
-                                    store.upsert(&*keys.get(i).unwrap(), &42, 1);
+                                    session.upsert(&*keys.get(i).unwrap(), &42).unwrap();
                                     upserts += 1;
                                 }
                                 Operation::Rmw => {
-                                    store.rmw(&*keys.get(i).unwrap(), &5, 1);
+                                    session.rmw(&*keys.get(i).unwrap(), &5).unwrap();
                                     rmws += 1;
                                 }
                             }
                         }
                     }
 
-                    store.complete_pending(true);
-                    store.stop_session();
+                    session.complete_pending(true).unwrap();
+                    drop(session);
                     let duration = Instant::now().duration_since(start);
 
                     println!(
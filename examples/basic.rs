@@ -1,26 +1,27 @@
 extern crate faster_rs;
 
-use faster_rs::{status, FasterKv};
+use faster_rs::{FasterError, FasterKv, Status};
 use local_channel::mpsc::Receiver;
 
 #[monoio::main]
 async fn main() {
     // Create a Key-Value Store
     let store = FasterKv::default();
+    let session = store.session();
     let key0: u64 = 1;
     let value0: u64 = 1000;
     let modification: u64 = 5;
 
     // Upsert
     for i in 0..1000 {
-        let upsert = store.upsert(&(key0 + i), &(value0 + i), i);
-        assert!(upsert == status::OK || upsert == status::PENDING);
+        let upsert = session.upsert(&(key0 + i), &(value0 + i)).unwrap();
+        assert!(upsert == Status::Ok || upsert == Status::Pending);
     }
 
     // Read-Modify-Write
     for i in 0..1000 {
-        let rmw = store.rmw(&(key0 + i), &(5 as u64), i + 1000);
-        assert!(rmw == status::OK || rmw == status::PENDING);
+        let rmw = session.rmw(&(key0 + i), &(5 as u64)).unwrap();
+        assert!(rmw == Status::Ok || rmw == Status::Pending);
     }
 
     assert!(store.size() > 0);
@@ -28,9 +29,9 @@ async fn main() {
     // Read
     for i in 0..1000 {
         // Note: need to provide type annotation for the Receiver
-        let (read, mut recv): (u8, Receiver<u64>) = store.read(&(key0 + i), i);
-        assert!(read == status::OK || read == status::PENDING);
-        let val = recv.recv().await.unwrap();
+        let (read, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&(key0 + i)).unwrap();
+        assert!(read == Status::Ok || read == Status::Pending);
+        let val = recv.recv().await.unwrap().unwrap();
         assert_eq!(val, value0 + i + modification);
         println!("Key: {}, Value: {}", key0 + i, val);
     }
@@ -1,7 +1,7 @@
 extern crate faster_rs;
 extern crate serde_derive;
 
-use faster_rs::{status, FasterKv};
+use faster_rs::{FasterError, FasterKv, Status};
 use serde_derive::{Deserialize, Serialize};
 use local_channel::mpsc::Receiver;
 
@@ -16,6 +16,7 @@ struct MyKey {
 async fn main() {
     // Create a Key-Value Store
     let store = FasterKv::default();
+    let session = store.session();
     let key = MyKey {
         foo: String::from("Hello"),
         bar: String::from("World"),
@@ -23,14 +24,14 @@ async fn main() {
     let value: u64 = 1;
 
     // Upsert
-    let upsert = store.upsert(&key, &value, 1);
-    assert!(upsert == status::OK || upsert == status::PENDING);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert!(upsert == Status::Ok || upsert == Status::Pending);
     assert!(store.size() > 0);
 
     // Note: need to provide type annotation for the Receiver
-    let (read, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert!(read == status::OK || read == status::PENDING);
-    let val = recv.recv().await.unwrap();
+    let (read, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert!(read == Status::Ok || read == Status::Pending);
+    let val = recv.recv().await.unwrap().unwrap();
     println!("Key: {:?}, Value: {}", key, val);
 
     // Clear used storage
@@ -34,18 +34,17 @@ async fn main() {
             );
             populate(num_threads).await;
         } else if operation == "recover" {
-            if args.len() > 3 {
-                let token = &args[3];
-                recover(token.to_string(), num_threads).await;
-            } else {
-                println!("Second argument required is checkpoint token to recover");
-            }
+            // Without a token, the latest checkpoint in the storage directory is recovered
+            let token = args
+                .get(3)
+                .map(|token| token.parse().expect("Checkpoint token must be a GUID"));
+            recover(token, num_threads).await;
         }
     } else {
         println!("Populate: args {}, {}", "1. populate", "2. #threads");
         println!(
             "Recover: args {}, {}, {}",
-            "1. recover", "2. #threads", "3. checkpoint token"
+            "1. recover", "2. #threads", "3. checkpoint token (optional)"
         );
     }
 }
@@ -60,29 +59,30 @@ async fn populate(num_threads: usize) -> () {
             let num_active_threads = Arc::clone(&num_active_threads);
             threads.push(std::thread::spawn(move || {
                 // Populate Store
-                let _session = store.start_session();
+                let session = store.session();
                 num_active_threads.fetch_add(1, Ordering::SeqCst);
 
                 for i in 0..NUM_OPS {
                     let idx = i as u64;
-                    store.rmw(&(idx % NUM_UNIQUE_KEYS), &(1 as u64), idx);
+                    session.rmw(&(idx % NUM_UNIQUE_KEYS), &(1 as u64)).unwrap();
 
                     if (idx % CHECKPOINT_INTERVAL == 0)
                         && num_active_threads.load(Ordering::SeqCst) == num_threads
                     {
                         let check = store.checkpoint().unwrap();
-                        println!("Calling checkpoint with token {}", check.token);
+                        if let Some(token) = check.token {
+                            println!("Calling checkpoint with token {}", token);
+                        }
                     }
 
                     if (idx % COMPLETE_PENDING_INTERVAL) == 0 {
-                        store.complete_pending(false);
+                        session.complete_pending(false).unwrap();
                     } else if (idx % REFRESH_INTERVAL) == 0 {
-                        store.refresh();
+                        session.refresh();
                     }
                 }
 
-                store.complete_pending(true);
-                store.stop_session();
+                drop(session);
                 println!("Thread {} finished populating", thread_id);
             }));
         }
@@ -93,18 +93,18 @@ async fn populate(num_threads: usize) -> () {
         println!("Store size: {}", store.size());
         println!("Verifying values");
 
-        store.start_session();
+        let session = store.session();
         let mut read_results = Vec::with_capacity(NUM_UNIQUE_KEYS as usize);
         for idx in 0..NUM_UNIQUE_KEYS {
-            let (_, receiver): (u8, Receiver<u64>) = store.read(&idx, idx);
+            let (_, receiver): (Status, Receiver<Result<u64, FasterError>>) = session.read(&idx).unwrap();
             read_results.insert(idx as usize, receiver);
         }
-        store.complete_pending(true);
-        store.stop_session();
+        session.complete_pending(true).unwrap();
+        drop(session);
 
         let expected_value: u64 = (num_threads as u64) * NUM_OPS / NUM_UNIQUE_KEYS;
         for idx in 0..NUM_UNIQUE_KEYS {
-            match read_results[idx as usize].recv().await {
+            match read_results[idx as usize].recv().await.transpose().unwrap() {
                 Some(val) => {
                     if val != expected_value {
                         println!(
@@ -123,10 +123,14 @@ async fn populate(num_threads: usize) -> () {
     }
 }
 
-async fn recover(token: String, num_threads: usize) -> () {
+async fn recover(token: Option<CheckpointToken>, num_threads: usize) -> () {
     println!("Attempting to recover");
     if let Ok(store) = FasterKvBuilder::new(TABLE_SIZE, LOG_SIZE).with_disk(STORAGE_DIR).build() {
-        match store.recover(token.clone(), token.clone()) {
+        let recovered = match token {
+            Some(token) => store.recover(token, token),
+            None => store.recover_latest(),
+        };
+        match recovered {
             Ok(rec) => {
                 println!("Recover version: {}", rec.version);
                 println!("Recover status: {}", rec.status);
@@ -134,27 +138,26 @@ async fn recover(token: String, num_threads: usize) -> () {
                 
                 let mut serial_nums = vec![];
                 for id in rec.session_ids {
-                    serial_nums.push(store.continue_session(id));
-                    store.stop_session();
+                    let (_, last_serial) = store.resume_session(id);
+                    serial_nums.push(last_serial);
                 }
 
-                store.start_session();
+                let session = store.session();
                 let mut read_results = Vec::with_capacity(NUM_UNIQUE_KEYS as usize);
                 for idx in 0..NUM_UNIQUE_KEYS {
-                    let (_, receiver): (u8, Receiver<u64>) = store.read(&idx, idx);
+                    let (_, receiver): (Status, Receiver<Result<u64, FasterError>>) = session.read(&idx).unwrap();
                     read_results.insert(idx as usize, receiver);
                 }
-                store.complete_pending(true);
-                store.stop_session();
+                session.complete_pending(true).unwrap();
 
 
                 println!("Generating expected values");
                 let mut expected_results = Vec::with_capacity(NUM_UNIQUE_KEYS as usize);
                 expected_results.resize(NUM_UNIQUE_KEYS as usize, 0);
 
-                // Sessions active during checkpoint
+                // Sessions active during checkpoint, whose operation i had serial number i + 1
                 for persisted_count in serial_nums.iter() {
-                    for i in 0..(persisted_count + 1) {
+                    for i in 0..*persisted_count {
                         let elem = expected_results
                             .get_mut((i % NUM_UNIQUE_KEYS) as usize)
                             .unwrap();
@@ -177,9 +180,9 @@ async fn recover(token: String, num_threads: usize) -> () {
                 let mut incorrect = 0;
                 for i in 0..NUM_OPS {
                     let idx = i as u64;
-                    let (status, mut recv): (u8, Receiver<u64>) =
-                        store.read(&(idx % NUM_UNIQUE_KEYS), idx);
-                    if let Some(val) = recv.recv().await {
+                    let (status, mut recv): (Status, Receiver<Result<u64, FasterError>>) =
+                        session.read(&(idx % NUM_UNIQUE_KEYS)).unwrap();
+                    if let Some(val) = recv.recv().await.transpose().unwrap() {
                         let expected = *expected_results
                             .get((idx % NUM_UNIQUE_KEYS) as usize)
                             .unwrap();
@@ -30,8 +30,10 @@ async fn main() {
             populate().await;
         } else if operation == "recover" {
             if args.len() > 2 {
-                let token = &args[2];
-                recover(token.to_string()).await;
+                match args[2].parse() {
+                    Ok(token) => recover(token).await,
+                    Err(err) => println!("{}: {}", err, args[2]),
+                }
             } else {
                 println!("Second argument required is token checkpoint to recover");
             }
@@ -49,55 +51,57 @@ async fn populate() -> () {
         .build()
     {
         // Populate Store
-        let session = store.start_session();
-        println!("Starting Session {}", session);
+        let session = store.session();
+        println!("Starting Session {}", session.id());
 
         for i in 0..NUM_OPS {
             let idx = i as u64;
-            store.rmw(&(idx % NUM_UNIQUE_KEYS), &(1u64), idx);
+            session.rmw(&(idx % NUM_UNIQUE_KEYS), &(1u64)).unwrap();
 
             if (idx % CHECKPOINT_INTERVAL) == 0 {
                 let check = store.checkpoint().unwrap();
-                println!("Calling checkpoint with token {}", check.token);
+                if let Some(token) = check.token {
+                    println!("Calling checkpoint with token {}", token);
+                }
             }
 
             if (idx % COMPLETE_PENDING_INTERVAL) == 0 {
-                store.complete_pending(false);
+                session.complete_pending(false).unwrap();
             } else if (idx % REFRESH_INTERVAL) == 0 {
-                store.refresh();
+                session.refresh();
             }
         }
 
         println!("Dumping distribution");
         store.dump_distribution();
-        println!("Stopping Session {}", session);
-        store.complete_pending(true);
-        store.stop_session();
+        println!("Stopping Session {}", session.id());
+        drop(session);
         println!("Store size: {}", store.size());
     } else {
         println!("Failed to create FasterKV store");
     }
 }
 
-async fn recover(token: String) -> () {
+async fn recover(token: CheckpointToken) -> () {
     println!("Attempting to recover");
     if let Ok(recover_store) = FasterKvBuilder::new(TABLE_SIZE, LOG_SIZE)
         .with_disk(STORAGE_DIR)
         .set_pre_allocate_log(true)
         .build()
     {
-        match recover_store.recover(token.clone(), token.clone()) {
+        match recover_store.recover(token, token) {
             Ok(rec) => {
                 println!("Recover version: {}", rec.version);
                 println!("Recover status: {}", rec.status);
                 println!("Recovered sessions: {:?}", rec.session_ids);
-                let persisted_count =
-                    recover_store.continue_session(rec.session_ids.first().cloned().unwrap());
+                let (session, persisted_count) =
+                    recover_store.resume_session(*rec.session_ids.first().unwrap());
                 println!("Session persisted until: {}", persisted_count);
 
                 let mut expected_results = Vec::with_capacity(NUM_UNIQUE_KEYS as usize);
                 expected_results.resize(NUM_UNIQUE_KEYS as usize, 0);
-                for i in 0..(persisted_count + 1) {
+                // The operation with index i was issued with serial number i + 1
+                for i in 0..persisted_count {
                     let elem = expected_results
                         .get_mut((i % NUM_UNIQUE_KEYS) as usize)
                         .unwrap();
@@ -108,9 +112,9 @@ async fn recover(token: String) -> () {
                 let mut incorrect = 0;
                 for i in 0..NUM_OPS {
                     let idx = i as u64;
-                    let (status, mut recv): (u8, Receiver<u64>) =
-                        recover_store.read(&(idx % NUM_UNIQUE_KEYS), idx);
-                    if let Some(val) = recv.recv().await {
+                    let (status, mut recv): (Status, Receiver<Result<u64, FasterError>>) =
+                        session.read(&(idx % NUM_UNIQUE_KEYS)).unwrap();
+                    if let Some(val) = recv.recv().await.transpose().unwrap() {
                         let expected = *expected_results
                             .get((idx % NUM_UNIQUE_KEYS) as usize)
                             .unwrap();
@@ -126,7 +130,6 @@ async fn recover(token: String) -> () {
                     }
                 }
                 println!("{} incorrect recoveries", incorrect);
-                recover_store.stop_session();
             }
             Err(_) => println!("Recover operation failed"),
         }
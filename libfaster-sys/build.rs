@@ -54,7 +54,20 @@ fn main() {
 
   faster_bindgen();
 
-  let dst = Config::new("FASTER/cc").cflag("--std=c++11 ").build();
+  let mut config = Config::new("FASTER/cc");
+  config.cflag("--std=c++11 ");
+  // e.g. FASTER_SANITIZER=address, to instrument FASTER along with a sanitized Rust build
+  println!("cargo:rerun-if-env-changed=FASTER_SANITIZER");
+  if let Ok(sanitizer) = env::var("FASTER_SANITIZER") {
+    config.cflag(format!("-fsanitize={}", sanitizer));
+    config.cxxflag(format!("-fsanitize={}", sanitizer));
+  }
+  // FASTER's io_uring handler is only compiled in on request, as it needs liburing
+  if env::var("CARGO_FEATURE_IO_URING").is_ok() {
+    config.define("USE_URING", "ON");
+    config.cxxflag("-DFASTER_URING");
+  }
+  let dst = config.build();
 
   println!("cargo:rustc-link-search=native={}/{}", dst.display(), "build");
   // Fix this...
@@ -65,6 +78,9 @@ fn main() {
   println!("cargo:rustc-link-lib=gcc");
   println!("cargo:rustc-link-lib=stdc++");
   println!("cargo:rustc-link-lib=aio");
+  if env::var("CARGO_FEATURE_IO_URING").is_ok() {
+    println!("cargo:rustc-link-lib=uring");
+  }
   println!("cargo:rustc-link-lib=pthread");
   println!("cargo:rustc-link-lib=m");
 }
@@ -1,4 +1,11 @@
+#include <cstdio>
+#include <cstdlib>
+#include <string>
+#include <thread>
+#include <unordered_map>
+#include <unordered_set>
 #include "FASTER/cc/src/core/faster.h"
+#include "FASTER/cc/src/core/log_scan.h"
 #include "faster-c.h"
 #include "FASTER/cc/src/device/file_system_disk.h"
 #include "FASTER/cc/src/device/null_disk.h"
@@ -6,29 +13,35 @@
 extern "C" {
 
   using namespace FASTER::core;
-  void deallocate_vec(uint8_t*, uint64_t);
+
+  /// Copies `length` bytes to the heap, for contexts that outlive the caller's buffers. Never
+  /// returns NULL, as NULL marks a key stored inline. A context is copied while FASTER is already
+  /// deferring its operation, which cannot be failed from here, so running out of memory aborts.
+  inline uint8_t* copy_to_heap(const uint8_t* src, uint64_t length) {
+    uint8_t* dst = (uint8_t*) malloc(length > 0 ? length : 1);
+    if (dst == NULL) {
+      fprintf(stderr, "faster-c: out of memory copying the buffers of a pending operation\n");
+      abort();
+    }
+    memcpy(dst, src, length);
+    return dst;
+  }
 
   class Key {
     public:
+      /// Borrows `key`, which must outlive this key. The caller's buffer is only borrowed for the
+      /// duration of an operation, so contexts copy it to the heap when they are deep-copied.
       Key(const uint8_t* key, const uint64_t key_length)
-        : temp_buffer_{ key }
-        , key_length_{ key_length } {
+        : key_length_{ key_length }
+        , temp_buffer_{ key } {
       }
 
+      /// FASTER copies keys into records, which reserve size() bytes for the key. The copy stores
+      /// the key bytes inline, right behind the key.
       Key(const Key& other) {
         key_length_ = other.key_length_;
         temp_buffer_ = NULL;
-        if (other.temp_buffer_ == NULL) {
-          memcpy(buffer(), other.buffer(), key_length_);
-        } else {
-          memcpy(buffer(), other.temp_buffer_, key_length_);
-        }
-      }
-
-      ~Key() {
-        if (this->temp_buffer_ != NULL) {
-          deallocate_vec((uint8_t*) this->temp_buffer_, this->key_length_);
-        }
+        memcpy(buffer(), other.data(), key_length_);
       }
 
       /// Methods and operators required by the (implicit) interface:
@@ -36,24 +49,23 @@ extern "C" {
         return static_cast<uint32_t>(sizeof(Key) + key_length_);
       }
       inline KeyHash GetHash() const {
-        if (this->temp_buffer_ != NULL) {
-          return KeyHash(Utility::Hash8BitBytes(temp_buffer_, key_length_));
-        }
-        return KeyHash(Utility::Hash8BitBytes(buffer(), key_length_));
+        return KeyHash(Utility::Hash8BitBytes(data(), key_length_));
       }
 
       /// Comparison operators.
       inline bool operator==(const Key& other) const {
         if (this->key_length_ != other.key_length_) return false;
-        const uint8_t* self_buffer = this->temp_buffer_ == NULL ? buffer() : this->temp_buffer_;
-        const uint8_t* other_buffer = other.temp_buffer_ == NULL ? other.buffer() : other.temp_buffer_;
-        return memcmp(self_buffer, other_buffer, key_length_) == 0;
+        return memcmp(data(), other.data(), key_length_) == 0;
       }
       inline bool operator!=(const Key& other) const {
-        if (this->key_length_ != other.key_length_) return true;
-        const uint8_t* self_buffer = this->temp_buffer_ == NULL ? buffer() : this->temp_buffer_;
-        const uint8_t* other_buffer = other.temp_buffer_ == NULL ? other.buffer() : other.temp_buffer_;
-        return memcmp(self_buffer, other_buffer, key_length_) != 0;
+        return !(*this == other);
+      }
+
+      inline const uint8_t* data() const {
+        return temp_buffer_ == NULL ? buffer() : temp_buffer_;
+      }
+      inline uint64_t length() const {
+        return key_length_;
       }
 
     private:
@@ -71,6 +83,9 @@ extern "C" {
   class ReadContext;
   class UpsertContext;
   class RmwContext;
+  class RmwWithContext;
+  class ConditionalContext;
+  class CompactionContext;
 
   class GenLock {
   public:
@@ -154,10 +169,19 @@ extern "C" {
     inline uint32_t size() const {
       return size_;
     }
+    inline const uint8_t* data() const {
+      return buffer();
+    }
+    inline uint64_t length() const {
+      return length_;
+    }
 
     friend class ReadContext;
     friend class UpsertContext;
     friend class RmwContext;
+    friend class RmwWithContext;
+    friend class ConditionalContext;
+    friend class CompactionContext;
 
   private:
     AtomicGenLock gen_lock_;
@@ -177,17 +201,27 @@ extern "C" {
     typedef Key key_t;
     typedef Value value_t;
 
-    ReadContext(const uint8_t* key, uint64_t key_length, read_callback cb, void* target)
-      : key_{ key, key_length }
+    ReadContext(const uint8_t* key, uint64_t key_length, read_callback cb, void* target,
+                read_relocate_callback relocate = NULL)
+      : owned_key_{ NULL }
+      , key_{ key, key_length }
       , cb_ { cb }
-      , target_ { target }  {
+      , target_ { target }
+      , relocate_ { relocate }  {
     }
 
-    /// Copy (and deep-copy) constructor.
+    /// Copy (and deep-copy) constructor. Copies the borrowed key to the heap and gives the caller a
+    /// chance to move a stack-allocated target before the original context goes out of scope.
     ReadContext(const ReadContext& other)
-      : key_{ other.key_ }
+      : owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , key_{ owned_key_, other.key_.length() }
       , cb_ { other.cb_ }
-      , target_ { other.target_ }  {
+      , target_ { other.relocate_ == NULL ? other.target_ : other.relocate_(other.target_) }
+      , relocate_ { other.relocate_ }  {
+    }
+
+    ~ReadContext() {
+      free(owned_key_);
     }
 
     /// The implicit and explicit interfaces require a key() accessor.
@@ -217,6 +251,10 @@ extern "C" {
     inline void ReturnNotFound() {
       cb_(target_, NULL, 0, NotFound);
     }
+    /// For async reads that failed
+    inline void ReturnError(Status result) {
+      cb_(target_, NULL, 0, static_cast<faster_status>(result));
+    }
 
   protected:
     /// The explicit interface requires a DeepCopy_Internal() implementation.
@@ -225,9 +263,11 @@ extern "C" {
     }
 
   private:
+    uint8_t* owned_key_;
     Key key_;
     read_callback cb_;
     void* target_;
+    read_relocate_callback relocate_;
   };
 
   class UpsertContext : public IAsyncContext {
@@ -235,24 +275,32 @@ extern "C" {
     typedef Key key_t;
     typedef Value value_t;
 
-    UpsertContext(const uint8_t* key, uint64_t key_length, uint8_t* input, uint64_t length)
-      : key_{ key, key_length }
+    UpsertContext(const uint8_t* key, uint64_t key_length, const uint8_t* input, uint64_t length,
+                  completion_callback done, void* target)
+      : owned_key_{ NULL }
+      , owned_input_{ NULL }
+      , key_{ key, key_length }
       , input_{ input }
-      , length_{ length } {
+      , length_{ length }
+      , done_{ done }
+      , target_{ target } {
     }
 
-    /// Copy (and deep-copy) constructor.
-    UpsertContext(UpsertContext& other)
-      : key_{ other.key_ }
-      , input_{ other.input_ }
-      , length_{ other.length_ } {
-      other.input_ = NULL;
+    /// Copy (and deep-copy) constructor. The caller's buffers are only borrowed, so the copy owns
+    /// copies of them.
+    UpsertContext(const UpsertContext& other)
+      : owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , owned_input_{ copy_to_heap(other.input_, other.length_) }
+      , key_{ owned_key_, other.key_.length() }
+      , input_{ owned_input_ }
+      , length_{ other.length_ }
+      , done_{ other.done_ }
+      , target_{ other.target_ } {
     }
 
     ~UpsertContext() {
-      if (input_ != NULL) {
-        deallocate_vec(input_, length_);
-      }
+      free(owned_key_);
+      free(owned_input_);
     }
 
     /// The implicit and explicit interfaces require a key() accessor.
@@ -290,6 +338,13 @@ extern "C" {
       return true;
     }
 
+    /// Notifies the caller of a context that went pending.
+    inline void Done(Status result) {
+      if (done_) {
+        done_(target_, static_cast<faster_status>(result));
+      }
+    }
+
   protected:
     /// The explicit interface requires a DeepCopy_Internal() implementation.
     Status DeepCopy_Internal(IAsyncContext*& context_copy) {
@@ -297,9 +352,13 @@ extern "C" {
     }
 
   private:
+    uint8_t* owned_key_;
+    uint8_t* owned_input_;
     key_t key_;
-    uint8_t* input_;
+    const uint8_t* input_;
     uint64_t length_;
+    completion_callback done_;
+    void* target_;
   };
 
   class RmwContext : public IAsyncContext {
@@ -307,28 +366,40 @@ extern "C" {
     typedef Key key_t;
     typedef Value value_t;
 
-    RmwContext(const uint8_t* key, uint64_t key_length, uint8_t* modification, uint64_t length, rmw_callback cb)
-      : key_{ key, key_length }
+    RmwContext(faster_t* faster, const uint8_t* key, uint64_t key_length, const uint8_t* modification,
+               uint64_t length, rmw_callback cb, completion_callback done, void* target)
+      : faster_{ faster }
+      , owned_key_{ NULL }
+      , owned_modification_{ NULL }
+      , key_{ key, key_length }
       , modification_{ modification }
       , length_{ length }
       , cb_{ cb }
-      , new_length_{ 0 }{
+      , new_length_{ 0 }
+      , done_{ done }
+      , target_{ target }
+      , creates_{ false } {
     }
 
-    /// Copy (and deep-copy) constructor.
-    RmwContext(RmwContext& other)
-      : key_{ other.key_ }
-      , modification_{ other.modification_ }
+    /// Copy (and deep-copy) constructor. The caller's buffers are only borrowed, so the copy owns
+    /// copies of them.
+    RmwContext(const RmwContext& other)
+      : faster_{ other.faster_ }
+      , owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , owned_modification_{ copy_to_heap(other.modification_, other.length_) }
+      , key_{ owned_key_, other.key_.length() }
+      , modification_{ owned_modification_ }
       , length_{ other.length_ }
       , cb_{ other.cb_ }
-      , new_length_{ other.new_length_ }{
-      other.modification_ = NULL;
+      , new_length_{ other.new_length_ }
+      , done_{ other.done_ }
+      , target_{ other.target_ }
+      , creates_{ other.creates_ } {
     }
 
     ~RmwContext() {
-      if (modification_ != NULL) {
-        deallocate_vec(modification_, length_);
-      }
+      free(owned_key_);
+      free(owned_modification_);
     }
 
     /// The implicit and explicit interfaces require a key() accessor.
@@ -336,24 +407,23 @@ extern "C" {
       return key_;
     }
     inline uint32_t value_size() const {
-      return sizeof(Value) + length_;
+      return sizeof(Value) + cb_(NULL, 0, modification_, length_, NULL, false);
     }
     inline uint32_t value_size(const Value& old_value) {
       if (new_length_ == 0) {
-        new_length_ = cb_(old_value.buffer(), old_value.length_, modification_, length_, NULL);
+        new_length_ = cb_(old_value.buffer(), old_value.length_, modification_, length_, NULL, false);
       }
       return sizeof(Value) + new_length_;
     }
 
     inline void RmwInitial(Value& value) {
       value.gen_lock_.store(0);
-      value.size_ = sizeof(Value) + length_;
-      value.length_ = length_;
-      std::memcpy(value.buffer(), modification_, length_);
+      value.length_ = cb_(NULL, 0, modification_, length_, value.buffer(), false);
+      value.size_ = sizeof(Value) + value.length_;
     }
     inline void RmwCopy(const Value& old_value, Value& value) {
       value.gen_lock_.store(0);
-      value.length_ = cb_(old_value.buffer(), old_value.length_, modification_, length_, value.buffer());
+      value.length_ = cb_(old_value.buffer(), old_value.length_, modification_, length_, value.buffer(), false);
       value.size_ = sizeof(Value) + value.length_;
     }
     inline bool RmwAtomic(Value& value) {
@@ -365,8 +435,15 @@ extern "C" {
         // Some other thread replaced this record.
         return false;
       }
+      // Fast path for values whose length does not grow, which are updated with a single call
+      uint64_t in_place_length = cb_(value.buffer(), value.length_, modification_, length_, value.buffer(), true);
+      if (in_place_length != FASTER_RMW_NOT_IN_PLACE) {
+        value.length_ = in_place_length;
+        value.gen_lock_.unlock(false);
+        return true;
+      }
       if (new_length_ == 0) {
-        new_length_ = cb_(value.buffer(), value.length_, modification_, length_, NULL);
+        new_length_ = cb_(value.buffer(), value.length_, modification_, length_, NULL, false);
       }
       if(value.size_ < sizeof(Value) + new_length_) {
         // Current value is too small for in-place update.
@@ -374,12 +451,31 @@ extern "C" {
         return false;
       }
       // In-place update overwrites length and buffer, but not size.
-      cb_(value.buffer(), value.length_, modification_, length_, value.buffer());
-      value.length_ = new_length_;
+      value.length_ = cb_(value.buffer(), value.length_, modification_, length_, value.buffer(), false);
       value.gen_lock_.unlock(false);
       return true;
     }
 
+    /// Notifies the caller of a context that went pending.
+    inline void Done(Status result) {
+      if (done_) {
+        done_(target_, static_cast<faster_status>(result));
+      }
+    }
+
+    /// Like for a RmwWithContext, the RMW only creates the record of a key without one once cb_ has
+    /// computed its value. Returns whether it succeeded.
+    inline bool Initial() {
+      creates_ = cb_(NULL, 0, modification_, length_, NULL, false) != FASTER_RMW_FAILED;
+      return creates_;
+    }
+    inline bool creates() const {
+      return creates_;
+    }
+    inline faster_t* faster() const {
+      return faster_;
+    }
+
   protected:
     /// The explicit interface requires a DeepCopy_Internal() implementation.
     Status DeepCopy_Internal(IAsyncContext*& context_copy) {
@@ -387,11 +483,302 @@ extern "C" {
     }
 
   private:
+    faster_t* faster_;
+    uint8_t* owned_key_;
+    uint8_t* owned_modification_;
     Key key_;
-    uint8_t* modification_;
+    const uint8_t* modification_;
     uint64_t length_;
     rmw_callback cb_;
     uint64_t new_length_;
+    completion_callback done_;
+    void* target_;
+    bool creates_;
+  };
+
+  class RmwWithContext : public IAsyncContext {
+  public:
+    typedef Key key_t;
+    typedef Value value_t;
+
+    RmwWithContext(faster_t* faster, const uint8_t* key, uint64_t key_length, rmw_with_callback cb,
+                   rmw_with_done_callback done, void* target, read_relocate_callback relocate)
+      : faster_{ faster }
+      , owned_key_{ NULL }
+      , key_{ key, key_length }
+      , cb_{ cb }
+      , done_{ done }
+      , target_{ target }
+      , relocate_{ relocate }
+      , creates_{ false }
+      , new_value_{ NULL }
+      , new_length_{ 0 } {
+    }
+
+    /// Copy (and deep-copy) constructor. Copies the borrowed key to the heap and gives the caller a
+    /// chance to move a stack-allocated target, like for a ReadContext.
+    RmwWithContext(const RmwWithContext& other)
+      : faster_{ other.faster_ }
+      , owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , key_{ owned_key_, other.key_.length() }
+      , cb_{ other.cb_ }
+      , done_{ other.done_ }
+      , target_{ other.relocate_(other.target_) }
+      , relocate_{ other.relocate_ }
+      , creates_{ other.creates_ }
+      , new_value_{ NULL }
+      , new_length_{ 0 } {
+    }
+
+    ~RmwWithContext() {
+      free(owned_key_);
+    }
+
+    /// The implicit and explicit interfaces require a key() accessor.
+    inline const Key& key() const {
+      return key_;
+    }
+    /// FASTER sizes a new record right before it calls RmwInitial() or RmwCopy() on it, so the new
+    /// value is computed here and written by those.
+    inline uint32_t value_size() const {
+      Compute(NULL, 0);
+      return sizeof(Value) + new_length_;
+    }
+    inline uint32_t value_size(const Value& old_value) const {
+      Compute(old_value.buffer(), old_value.length_);
+      return sizeof(Value) + new_length_;
+    }
+
+    inline void RmwInitial(Value& value) {
+      Write(value);
+    }
+    inline void RmwCopy(const Value& old_value, Value& value) {
+      Write(value);
+    }
+    inline bool RmwAtomic(Value& value) {
+      bool replaced;
+      while(!value.gen_lock_.try_lock(replaced) && !replaced) {
+        std::this_thread::yield();
+      }
+      if(replaced) {
+        // Some other thread replaced this record.
+        return false;
+      }
+      Compute(value.buffer(), value.length_);
+      if(value.size_ < sizeof(Value) + new_length_) {
+        // Current value is too small for in-place update.
+        value.gen_lock_.unlock(true);
+        return false;
+      }
+      // In-place update overwrites length and buffer, but not size.
+      value.length_ = new_length_;
+      std::memcpy(value.buffer(), new_value_, new_length_);
+      value.gen_lock_.unlock(false);
+      return true;
+    }
+
+    /// Releases the target of a context that went pending.
+    inline void Done(Status result) {
+      done_(target_, static_cast<faster_status>(result));
+    }
+
+    /// The RMW only creates a record once the value of a key without one has been computed by this, so that
+    /// a key is left without a record if cb_ fails to compute it. Returns whether it succeeded.
+    inline bool Initial() {
+      Compute(NULL, 0);
+      creates_ = new_length_ != FASTER_RMW_FAILED;
+      return creates_;
+    }
+    inline bool creates() const {
+      return creates_;
+    }
+    inline faster_t* faster() const {
+      return faster_;
+    }
+
+  protected:
+    /// The explicit interface requires a DeepCopy_Internal() implementation.
+    Status DeepCopy_Internal(IAsyncContext*& context_copy) {
+      return IAsyncContext::DeepCopy_Internal(*this, context_copy);
+    }
+
+  private:
+    inline void Compute(const uint8_t* current, uint64_t length) const {
+      new_length_ = cb_(target_, current, length, &new_value_);
+    }
+    inline void Write(Value& value) {
+      value.gen_lock_.store(0);
+      value.size_ = sizeof(Value) + new_length_;
+      value.length_ = new_length_;
+      std::memcpy(value.buffer(), new_value_, new_length_);
+    }
+
+    faster_t* faster_;
+    uint8_t* owned_key_;
+    Key key_;
+    rmw_with_callback cb_;
+    rmw_with_done_callback done_;
+    void* target_;
+    read_relocate_callback relocate_;
+    bool creates_;
+    // The last value computed by cb_, owned by the target
+    mutable const uint8_t* new_value_;
+    mutable uint64_t new_length_;
+  };
+
+  class ConditionalContext : public IAsyncContext {
+  public:
+    typedef Key key_t;
+    typedef Value value_t;
+
+    /// Writes `value` if the current value is equal to `expected`, or if there is no record when `expected` is
+    /// NULL.
+    ConditionalContext(const uint8_t* key, uint64_t key_length, const uint8_t* expected, uint64_t expected_length,
+                       const uint8_t* value, uint64_t length, conditional_callback cb, void* target,
+                       read_relocate_callback relocate)
+      : owned_key_{ NULL }
+      , owned_expected_{ NULL }
+      , owned_value_{ NULL }
+      , key_{ key, key_length }
+      , expected_{ expected }
+      , expected_length_{ expected_length }
+      , value_{ value }
+      , length_{ length }
+      , cb_{ cb }
+      , target_{ target }
+      , relocate_{ relocate }
+      , current_{ NULL }
+      , current_length_{ 0 }
+      , swapped_{ false } {
+    }
+
+    /// Copy (and deep-copy) constructor. The caller's buffers are only borrowed, so the copy owns
+    /// copies of them, and the caller may move a stack-allocated target like for a ReadContext.
+    ConditionalContext(const ConditionalContext& other)
+      : owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , owned_expected_{ other.expected_ == NULL ? NULL : copy_to_heap(other.expected_, other.expected_length_) }
+      , owned_value_{ copy_to_heap(other.value_, other.length_) }
+      , key_{ owned_key_, other.key_.length() }
+      , expected_{ owned_expected_ }
+      , expected_length_{ other.expected_length_ }
+      , value_{ owned_value_ }
+      , length_{ other.length_ }
+      , cb_{ other.cb_ }
+      , target_{ other.relocate_ == NULL ? other.target_ : other.relocate_(other.target_) }
+      , relocate_{ other.relocate_ }
+      , current_{ NULL }
+      , current_length_{ 0 }
+      , swapped_{ false } {
+    }
+
+    ~ConditionalContext() {
+      free(owned_key_);
+      free(owned_expected_);
+      free(owned_value_);
+      free(current_);
+    }
+
+    /// The implicit and explicit interfaces require a key() accessor.
+    inline const Key& key() const {
+      return key_;
+    }
+    inline uint32_t value_size() const {
+      return sizeof(Value) + length_;
+    }
+    inline uint32_t value_size(const Value& old_value) {
+      return sizeof(Value) + (Matches(old_value) ? length_ : old_value.length_);
+    }
+
+    /// Only reached by faster_insert_if_absent, as faster_compare_and_swap does not create records.
+    inline void RmwInitial(Value& value) {
+      Write(value);
+      free(current_);
+      current_ = NULL;
+      current_length_ = 0;
+      swapped_ = true;
+    }
+    /// A record that does not match is copied as it is, as an RMW cannot be aborted at this point. So a failed
+    /// condition on a read-only record leaves the value unchanged, but still appends a record to the tail.
+    inline void RmwCopy(const Value& old_value, Value& value) {
+      Observe(old_value);
+      if (swapped_) {
+        Write(value);
+      } else {
+        value.gen_lock_.store(0);
+        value.size_ = sizeof(Value) + old_value.length_;
+        value.length_ = old_value.length_;
+        std::memcpy(value.buffer(), old_value.buffer(), old_value.length_);
+      }
+    }
+    inline bool RmwAtomic(Value& value) {
+      bool replaced;
+      while(!value.gen_lock_.try_lock(replaced) && !replaced) {
+        std::this_thread::yield();
+      }
+      if(replaced) {
+        // Some other thread replaced this record.
+        return false;
+      }
+      if(Matches(value) && value.size_ < sizeof(Value) + length_) {
+        // Current value is too small for in-place update.
+        value.gen_lock_.unlock(true);
+        return false;
+      }
+      Observe(value);
+      if (swapped_) {
+        // In-place update overwrites length and buffer, but not size.
+        value.length_ = length_;
+        std::memcpy(value.buffer(), value_, length_);
+      }
+      value.gen_lock_.unlock(false);
+      return true;
+    }
+
+    /// Reports the outcome once the operation has completed. FASTER may retry the update of a record, so the
+    /// outcome is only reported from here.
+    inline void Return(Status result) {
+      cb_(target_, current_, current_length_, swapped_, static_cast<faster_status>(result));
+    }
+
+  protected:
+    /// The explicit interface requires a DeepCopy_Internal() implementation.
+    Status DeepCopy_Internal(IAsyncContext*& context_copy) {
+      return IAsyncContext::DeepCopy_Internal(*this, context_copy);
+    }
+
+  private:
+    inline bool Matches(const Value& value) const {
+      return expected_ != NULL && value.length_ == expected_length_ &&
+             std::memcmp(value.buffer(), expected_, expected_length_) == 0;
+    }
+    inline void Write(Value& value) {
+      value.gen_lock_.store(0);
+      value.size_ = sizeof(Value) + length_;
+      value.length_ = length_;
+      std::memcpy(value.buffer(), value_, length_);
+    }
+    /// Keeps a copy of the current value for Return(), and decides whether to swap it.
+    inline void Observe(const Value& value) {
+      current_ = (uint8_t*) realloc(current_, value.length_ > 0 ? value.length_ : 1);
+      std::memcpy(current_, value.buffer(), value.length_);
+      current_length_ = value.length_;
+      swapped_ = Matches(value);
+    }
+
+    uint8_t* owned_key_;
+    uint8_t* owned_expected_;
+    uint8_t* owned_value_;
+    Key key_;
+    const uint8_t* expected_;
+    uint64_t expected_length_;
+    const uint8_t* value_;
+    uint64_t length_;
+    conditional_callback cb_;
+    void* target_;
+    read_relocate_callback relocate_;
+    uint8_t* current_;
+    uint64_t current_length_;
+    bool swapped_;
   };
 
   class DeleteContext : public IAsyncContext {
@@ -399,14 +786,25 @@ extern "C" {
       typedef Key key_t;
       typedef Value value_t;
 
-      DeleteContext(const uint8_t* key, uint64_t key_length)
-      : key_{ key, key_length } {
+      DeleteContext(const uint8_t* key, uint64_t key_length, completion_callback done, void* target)
+      : owned_key_{ NULL }
+      , key_{ key, key_length }
+      , done_{ done }
+      , target_{ target } {
 
       }
 
-      /// Copy (and deep-copy) constructor.
-      DeleteContext(DeleteContext& other)
-      : key_ { other.key_ } {
+      /// Copy (and deep-copy) constructor. The caller's key is only borrowed, so the copy owns a
+      /// copy of it.
+      DeleteContext(const DeleteContext& other)
+      : owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , key_ { owned_key_, other.key_.length() }
+      , done_{ other.done_ }
+      , target_{ other.target_ } {
+      }
+
+      ~DeleteContext() {
+        free(owned_key_);
       }
 
       /// The implicit and explicit interfaces require a key() accessor.
@@ -417,6 +815,13 @@ extern "C" {
         return sizeof(value_t);
       }
 
+      /// Notifies the caller of a context that went pending.
+      inline void Done(Status result) {
+        if (done_) {
+          done_(target_, static_cast<faster_status>(result));
+        }
+      }
+
   protected:
       /// The explicit interface requires a DeepCopy_Internal() implementation.
       Status DeepCopy_Internal(IAsyncContext*& context_copy) {
@@ -424,101 +829,304 @@ extern "C" {
       }
 
   private:
+      uint8_t* owned_key_;
       key_t key_;
+      completion_callback done_;
+      void* target_;
   };
 
-  enum store_type {
-      NULL_DISK,
-      FILESYSTEM_DISK,
+  /// Copies the current value of a key to the tail of the log, for compaction. Its RMW does not create records, so
+  /// a key that was deleted in the meantime stays deleted. Compaction makes the log below its address read-only
+  /// first, so a record in the mutable region was written since and is kept where it is.
+  class CompactionContext : public IAsyncContext {
+  public:
+    typedef Key key_t;
+    typedef Value value_t;
+
+    CompactionContext(const uint8_t* key, uint64_t key_length)
+      : owned_key_{ NULL }
+      , key_{ key, key_length } {
+    }
+
+    /// Copy (and deep-copy) constructor. The key is only borrowed, so the copy owns a copy of it.
+    CompactionContext(const CompactionContext& other)
+      : owned_key_{ copy_to_heap(other.key_.data(), other.key_.length()) }
+      , key_{ owned_key_, other.key_.length() } {
+    }
+
+    ~CompactionContext() {
+      free(owned_key_);
+    }
+
+    /// The implicit and explicit interfaces require a key() accessor.
+    inline const Key& key() const {
+      return key_;
+    }
+    inline uint32_t value_size() const {
+      return sizeof(Value);
+    }
+    inline uint32_t value_size(const Value& old_value) const {
+      return sizeof(Value) + old_value.length_;
+    }
+
+    /// Never reached, as the RMW does not create records.
+    inline void RmwInitial(Value& value) {
+      value.gen_lock_.store(0);
+      value.size_ = sizeof(Value);
+      value.length_ = 0;
+    }
+    inline void RmwCopy(const Value& old_value, Value& value) {
+      value.gen_lock_.store(0);
+      value.size_ = sizeof(Value) + old_value.length_;
+      value.length_ = old_value.length_;
+      std::memcpy(value.buffer(), old_value.buffer(), old_value.length_);
+    }
+    inline bool RmwAtomic(Value& value) {
+      return true;
+    }
+
+  protected:
+    /// The explicit interface requires a DeepCopy_Internal() implementation.
+    Status DeepCopy_Internal(IAsyncContext*& context_copy) {
+      return IAsyncContext::DeepCopy_Internal(*this, context_copy);
+    }
+
+  private:
+    uint8_t* owned_key_;
+    Key key_;
   };
-  typedef enum store_type store_type;
 
-  typedef FASTER::environment::QueueIoHandler handler_t;
-  typedef FASTER::device::FileSystemDisk<handler_t, 1073741824L> disk_t;
   typedef FASTER::device::NullDisk  disk_null_t;
-  using store_t = FasterKv<Key, Value, disk_t>;
   using null_store_t = FasterKv<Key, Value, disk_null_t>;
+
   struct faster_t {
-      union {
-          store_t* store;
-          null_store_t* null_store;
-      } obj;
-      store_type type;
+      // A FasterKv whose type depends on the device and segment size, see with_store
+      void* store;
+      faster_device_type device;
+      uint32_t segment_bits;
+      checkpoint_callback checkpoint_cb;
+      void* checkpoint_ctx;
   };
 
+  extern "C++" {
+    // The segment size is a template parameter of FileSystemDisk, so every supported segment size of
+    // file-backed stores is a separate instantiation of FasterKv. Sizes range from 16 MiB to 16 GiB.
+    constexpr uint32_t kMinSegmentBits = 24;
+    constexpr uint32_t kMaxSegmentBits = 34;
+
+    template <class H, uint32_t kBits>
+    using file_store_t = FasterKv<Key, Value, FASTER::device::FileSystemDisk<H, static_cast<uint64_t>(1) << kBits>>;
+
+    /// The file-backed stores with I/O handler H and segment sizes from 2^kBits bytes on.
+    template <class H, uint32_t kBits = kMinSegmentBits>
+    struct FileStores {
+      template <class F>
+      static auto with(faster_t* faster_t, F f) -> decltype(f(static_cast<file_store_t<H, kBits>*>(nullptr))) {
+        if (faster_t->segment_bits != kBits) {
+          return FileStores<H, kBits + 1>::with(faster_t, f);
+        }
+        return f(static_cast<file_store_t<H, kBits>*>(faster_t->store));
+      }
+
+      static void* open(uint32_t segment_bits, uint64_t table_size, uint64_t log_size, const char* storage,
+                        double log_mutable_fraction, bool pre_allocate_log) {
+        if (segment_bits != kBits) {
+          return FileStores<H, kBits + 1>::open(segment_bits, table_size, log_size, storage, log_mutable_fraction,
+                                                pre_allocate_log);
+        }
+        return new file_store_t<H, kBits> { table_size, log_size, storage, log_mutable_fraction, pre_allocate_log };
+      }
+    };
+
+    template <class H>
+    struct FileStores<H, kMaxSegmentBits> {
+      template <class F>
+      static auto with(faster_t* faster_t, F f) -> decltype(f(static_cast<file_store_t<H, kMaxSegmentBits>*>(nullptr))) {
+        return f(static_cast<file_store_t<H, kMaxSegmentBits>*>(faster_t->store));
+      }
+
+      static void* open(uint32_t segment_bits, uint64_t table_size, uint64_t log_size, const char* storage,
+                        double log_mutable_fraction, bool pre_allocate_log) {
+        return new file_store_t<H, kMaxSegmentBits> { table_size, log_size, storage, log_mutable_fraction,
+                                                      pre_allocate_log };
+      }
+    };
+
+    /// Calls `f` with the store of `faster_t`, cast to its actual type.
+    template <class F>
+    auto with_store(faster_t* faster_t, F f) -> decltype(f(static_cast<null_store_t*>(nullptr))) {
+      switch (faster_t->device) {
+        case FileSystemDevice:
+          return FileStores<FASTER::environment::QueueIoHandler>::with(faster_t, f);
+#ifdef FASTER_URING
+        case IoUringDevice:
+          return FileStores<FASTER::environment::UringIoHandler>::with(faster_t, f);
+#endif
+        default:
+          return f(static_cast<null_store_t*>(faster_t->store));
+      }
+    }
+  }
+
+  // FASTER's persistence callbacks carry no context, but they run on a thread that is inside a call
+  // into this store. Every entry point that may drive a checkpoint records its store here.
+  static thread_local faster_t* active_faster = NULL;
+
+  // The serial number of the last operation of the session of each store on this thread, so that operations the
+  // wrapper issues on its own behalf continue the session's sequence
+  static thread_local std::unordered_map<const faster_t*, uint64_t> session_serials;
+
+  inline void notify_checkpoint(faster_checkpoint_kind kind, Status result, uint64_t persistent_serial_num) {
+    faster_t* faster_t = active_faster;
+    if (faster_t != NULL && faster_t->checkpoint_cb != NULL) {
+      faster_t->checkpoint_cb(faster_t->checkpoint_ctx, kind, static_cast<faster_status>(result),
+                              persistent_serial_num);
+    }
+  }
+
+  void faster_set_checkpoint_callback(faster_t* faster_t, checkpoint_callback cb, void* ctx) {
+    faster_t->checkpoint_cb = cb;
+    faster_t->checkpoint_ctx = ctx;
+  }
+
   faster_t* faster_open(const uint64_t table_size, const uint64_t log_size, bool pre_allocate_log = false) {
-    faster_t* res = new faster_t();
-    res->obj.null_store = new null_store_t { table_size, log_size, "", 1.0, pre_allocate_log };
-    res->type = NULL_DISK;
-    return res;
+    return faster_open_with_device(table_size, log_size, NullDevice, "", 0, 1.0, pre_allocate_log);
   }
 
   faster_t* faster_open_with_disk(const uint64_t table_size, const uint64_t log_size,
                                   const char* storage, double log_mutable_fraction = 0.9, bool pre_allocate_log = false) {
+    return faster_open_with_device(table_size, log_size, FileSystemDevice, storage, 1L << 30, log_mutable_fraction,
+                                   pre_allocate_log);
+  }
+
+  faster_t* faster_open_with_device(const uint64_t table_size, const uint64_t log_size, faster_device_type device,
+                                    const char* storage, uint64_t segment_size, double log_mutable_fraction,
+                                    bool pre_allocate_log) {
+    uint32_t segment_bits = 0;
+    if (device != NullDevice) {
+      while (segment_bits < 64 && (static_cast<uint64_t>(1) << segment_bits) < segment_size) {
+        segment_bits++;
+      }
+      if (segment_bits < kMinSegmentBits || segment_bits > kMaxSegmentBits ||
+          (static_cast<uint64_t>(1) << segment_bits) != segment_size) {
+        return NULL;
+      }
+    }
+
     faster_t* res = new faster_t();
-    std::experimental::filesystem::create_directory(storage);
-    res->obj.store= new store_t { table_size, log_size, storage, log_mutable_fraction, pre_allocate_log };
-    res->type = FILESYSTEM_DISK;
+    res->device = device;
+    res->segment_bits = segment_bits;
+    switch (device) {
+      case NullDevice:
+        res->store = new null_store_t { table_size, log_size, "", 1.0, pre_allocate_log };
+        break;
+      case FileSystemDevice:
+        std::experimental::filesystem::create_directory(storage);
+        res->store = FileStores<FASTER::environment::QueueIoHandler>::open(segment_bits, table_size, log_size,
+                                                                          storage, log_mutable_fraction,
+                                                                          pre_allocate_log);
+        break;
+      case IoUringDevice:
+#ifdef FASTER_URING
+        std::experimental::filesystem::create_directory(storage);
+        res->store = FileStores<FASTER::environment::UringIoHandler>::open(segment_bits, table_size, log_size,
+                                                                          storage, log_mutable_fraction,
+                                                                          pre_allocate_log);
+        break;
+#else
+        delete res;
+        return NULL;
+#endif
+    }
     return res;
   }
 
   uint8_t faster_upsert(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
-                        uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number) {
+                        const uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number,
+                        completion_callback done, void* target) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
     auto callback = [](IAsyncContext* ctxt, Status result) {
-      assert(result == Status::Ok);
+      CallbackContext<UpsertContext> context { ctxt };
+      context->Done(result);
     };
 
-    UpsertContext context { key, key_length, value, value_length };
-    Status result;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        result = faster_t->obj.null_store->Upsert(context, callback, monotonic_serial_number);
-        break;
-      case FILESYSTEM_DISK:
-        result = faster_t->obj.store->Upsert(context, callback, monotonic_serial_number);
-        break;
-    }
+    UpsertContext context { key, key_length, value, value_length, done, target };
+    Status result = with_store(faster_t, [&](auto* store) {
+      return store->Upsert(context, callback, monotonic_serial_number);
+    });
     return static_cast<uint8_t>(result);
   }
 
-  uint8_t faster_rmw(faster_t* faster_t, const uint8_t* key, const uint64_t key_length, uint8_t* modification,
-                     const uint64_t length, const uint64_t monotonic_serial_number, rmw_callback cb) {
+  /// Updates an existing record first, and only creates the record of a key without one once its initial
+  /// value has been computed, so that a failing computation does not leave an empty record behind. Takes a
+  /// RmwContext or a RmwWithContext.
+  template <class C>
+  inline Status rmw_or_create(faster_t* faster_t, C& context, const uint64_t monotonic_serial_number) {
     auto callback = [](IAsyncContext* ctxt, Status result) {
-      CallbackContext<RmwContext> context { ctxt };
+      CallbackContext<C> context { ctxt };
+      if (result == Status::NotFound && !context->creates() && context->Initial()) {
+        // Called on the session's thread by faster_complete_pending, which completes the new RMW as well
+        auto faster = context->faster();
+        result = rmw_or_create(faster, *context, session_serials[faster]);
+        if (result == Status::Pending) {
+          // The copy of the context that went pending calls Done() instead
+          return;
+        }
+      }
+      context->Done(result);
     };
 
-    RmwContext context{ key, key_length, modification, length, cb};
-    Status result;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        result = faster_t->obj.null_store->Rmw(context, callback, monotonic_serial_number);
-        break;
-      case FILESYSTEM_DISK:
-        result = faster_t->obj.store->Rmw(context, callback, monotonic_serial_number);
-        break;
+    Status result = with_store(faster_t, [&](auto* store) {
+      return store->Rmw(context, callback, monotonic_serial_number, context.creates());
+    });
+    if (result == Status::NotFound && !context.creates() && context.Initial()) {
+      return rmw_or_create(faster_t, context, monotonic_serial_number);
     }
-    return static_cast<uint8_t>(result);
+    return result;
+  }
+
+  uint8_t faster_rmw(faster_t* faster_t, const uint8_t* key, const uint64_t key_length, const uint8_t* modification,
+                     const uint64_t length, const uint64_t monotonic_serial_number, rmw_callback cb,
+                     completion_callback done, void* target) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
+    RmwContext context{ faster_t, key, key_length, modification, length, cb, done, target };
+    return static_cast<uint8_t>(rmw_or_create(faster_t, context, monotonic_serial_number));
+  }
+
+  uint8_t faster_rmw_with(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                          const uint64_t monotonic_serial_number, rmw_with_callback cb,
+                          read_relocate_callback relocate, rmw_with_done_callback done, void* target) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
+    RmwWithContext context{ faster_t, key, key_length, cb, done, target, relocate };
+    return static_cast<uint8_t>(rmw_or_create(faster_t, context, monotonic_serial_number));
   }
 
   uint8_t faster_read(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
                        const uint64_t monotonic_serial_number, read_callback cb, void* target) {
+    return faster_read_with(faster_t, key, key_length, monotonic_serial_number, cb, NULL, target);
+  }
+
+  uint8_t faster_read_with(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                           const uint64_t monotonic_serial_number, read_callback cb,
+                           read_relocate_callback relocate, void* target) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
     auto callback = [](IAsyncContext* ctxt, Status result) {
       CallbackContext<ReadContext> context { ctxt };
       if (result == Status::NotFound) {
         context->ReturnNotFound();
+      } else if (result != Status::Ok) {
+        context->ReturnError(result);
       }
     };
 
-    ReadContext context {key, key_length, cb, target};
-    Status result;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        result = faster_t->obj.null_store->Read(context, callback, monotonic_serial_number);
-        break;
-      case FILESYSTEM_DISK:
-        result = faster_t->obj.store->Read(context, callback, monotonic_serial_number);
-        break;
-    }
+    ReadContext context {key, key_length, cb, target, relocate};
+    Status result = with_store(faster_t, [&](auto* store) {
+      return store->Read(context, callback, monotonic_serial_number);
+    });
 
     if (result == Status::NotFound) {
       cb(target, NULL, 0, NotFound);
@@ -527,43 +1135,71 @@ extern "C" {
     return static_cast<uint8_t>(result);
   }
 
+  inline uint8_t conditional_write(faster_t* faster_t, ConditionalContext& context,
+                                   const uint64_t monotonic_serial_number, bool create_if_not_exists) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
+    auto callback = [](IAsyncContext* ctxt, Status result) {
+      CallbackContext<ConditionalContext> context { ctxt };
+      context->Return(result);
+    };
+
+    Status result = with_store(faster_t, [&](auto* store) {
+      return store->Rmw(context, callback, monotonic_serial_number, create_if_not_exists);
+    });
+    if (result != Status::Pending) {
+      context.Return(result);
+    }
+    return static_cast<uint8_t>(result);
+  }
+
+  uint8_t faster_compare_and_swap(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                                  const uint8_t* expected, uint64_t expected_length, const uint8_t* value,
+                                  uint64_t value_length, const uint64_t monotonic_serial_number,
+                                  conditional_callback cb, read_relocate_callback relocate, void* target) {
+    ConditionalContext context { key, key_length, expected, expected_length, value, value_length, cb, target,
+                                 relocate };
+    return conditional_write(faster_t, context, monotonic_serial_number, false);
+  }
+
+  uint8_t faster_insert_if_absent(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                                  const uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number,
+                                  conditional_callback cb, read_relocate_callback relocate, void* target) {
+    ConditionalContext context { key, key_length, NULL, 0, value, value_length, cb, target, relocate };
+    return conditional_write(faster_t, context, monotonic_serial_number, true);
+  }
+
   uint8_t faster_delete(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
-                        const uint64_t monotonic_serial_number) {
+                        const uint64_t monotonic_serial_number, completion_callback done, void* target) {
+    active_faster = faster_t;
+    session_serials[faster_t] = monotonic_serial_number;
     auto callback = [](IAsyncContext* ctxt, Status result) {
       CallbackContext<DeleteContext> context { ctxt };
-      assert(result == Status::Ok || result == Status::NotFound);
+      context->Done(result);
     };
 
-    DeleteContext context {key, key_length};
-    Status result;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        result = faster_t->obj.null_store->Delete(context, callback, monotonic_serial_number);
-        break;
-      case FILESYSTEM_DISK:
-        result = faster_t->obj.store->Delete(context, callback, monotonic_serial_number);
-        break;
-    }
+    DeleteContext context {key, key_length, done, target};
+    Status result = with_store(faster_t, [&](auto* store) {
+      return store->Delete(context, callback, monotonic_serial_number);
+    });
     return static_cast<uint8_t>(result);
   }
 
   // It is up to the caller to dealloc faster_checkpoint_result*
   // first token, then struct
   faster_checkpoint_result* faster_checkpoint(faster_t* faster_t) {
+    active_faster = faster_t;
+    auto index_persistence_callback = [](Status result) {
+      notify_checkpoint(IndexCheckpoint, result, 0);
+    };
     auto hybrid_log_persistence_callback = [](Status result, uint64_t persistent_serial_num) {
-      assert(result == Status::Ok);
+      notify_checkpoint(HybridLogCheckpoint, result, persistent_serial_num);
     };
 
     Guid token;
-    bool checked;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        checked = faster_t->obj.null_store->Checkpoint(nullptr, hybrid_log_persistence_callback, token);
-        break;
-      case FILESYSTEM_DISK:
-        checked = faster_t->obj.store->Checkpoint(nullptr, hybrid_log_persistence_callback, token);
-        break;
-    }
+    bool checked = with_store(faster_t, [&](auto* store) {
+      return store->Checkpoint(index_persistence_callback, hybrid_log_persistence_callback, token);
+    });
     faster_checkpoint_result* res = (faster_checkpoint_result*) malloc(sizeof(faster_checkpoint_result));
     res->checked = checked;
     res->token = (char*) malloc(37 * sizeof(char));
@@ -574,20 +1210,15 @@ extern "C" {
   // It is up to the caller to dealloc faster_checkpoint_result*
   // first token, then struct
   faster_checkpoint_result* faster_checkpoint_index(faster_t* faster_t) {
+    active_faster = faster_t;
     auto index_persistence_callback = [](Status result) {
-        assert(result == Status::Ok);
+        notify_checkpoint(IndexCheckpoint, result, 0);
     };
 
     Guid token;
-    bool checked;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        checked = faster_t->obj.null_store->CheckpointIndex(index_persistence_callback, token);
-        break;
-      case FILESYSTEM_DISK:
-        checked = faster_t->obj.store->CheckpointIndex(index_persistence_callback, token);
-        break;
-    }
+    bool checked = with_store(faster_t, [&](auto* store) {
+      return store->CheckpointIndex(index_persistence_callback, token);
+    });
     faster_checkpoint_result* res = (faster_checkpoint_result*) malloc(sizeof(faster_checkpoint_result));
     res->checked = checked;
     res->token = (char*) malloc(37 * sizeof(char));
@@ -598,20 +1229,15 @@ extern "C" {
   // It is up to the caller to dealloc faster_checkpoint_result*
   // first token, then struct
   faster_checkpoint_result* faster_checkpoint_hybrid_log(faster_t* faster_t) {
+    active_faster = faster_t;
     auto hybrid_log_persistence_callback = [](Status result, uint64_t persistent_serial_num) {
-        assert(result == Status::Ok);
+        notify_checkpoint(HybridLogCheckpoint, result, persistent_serial_num);
     };
 
     Guid token;
-    bool checked;
-    switch (faster_t->type) {
-      case NULL_DISK:
-        checked = faster_t->obj.null_store->CheckpointHybridLog(hybrid_log_persistence_callback, token);
-        break;
-      case FILESYSTEM_DISK:
-        checked = faster_t->obj.store->CheckpointHybridLog(hybrid_log_persistence_callback, token);
-        break;
-    }
+    bool checked = with_store(faster_t, [&](auto* store) {
+      return store->CheckpointHybridLog(hybrid_log_persistence_callback, token);
+    });
     faster_checkpoint_result* res = (faster_checkpoint_result*) malloc(sizeof(faster_checkpoint_result));
     res->checked = checked;
     res->token = (char*) malloc(37 * sizeof(char));
@@ -622,15 +1248,12 @@ extern "C" {
   void faster_destroy(faster_t *faster_t) {
     if (faster_t == NULL)
       return;
+    if (active_faster == faster_t)
+      active_faster = NULL;
 
-    switch (faster_t->type) {
-      case NULL_DISK:
-        delete faster_t->obj.null_store;
-        break;
-      case FILESYSTEM_DISK:
-        delete faster_t->obj.store;
-        break;
-    }
+    with_store(faster_t, [](auto* store) {
+      delete store;
+    });
     delete faster_t;
   }
 
@@ -638,12 +1261,9 @@ extern "C" {
     if (faster_t == NULL) {
       return -1;
     } else {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          return faster_t->obj.null_store->Size();
-        case FILESYSTEM_DISK:
-          return faster_t->obj.store->Size();
-      }
+      return with_store(faster_t, [](auto* store) {
+        return store->Size();
+      });
     }
   }
 
@@ -660,15 +1280,9 @@ extern "C" {
       //TODO: error handling
       Guid index_guid = Guid::Parse(index_str);
       Guid hybrid_guid = Guid::Parse(hybrid_str);
-      Status sres;
-      switch (faster_t->type) {
-        case NULL_DISK:
-          sres = faster_t->obj.null_store->Recover(index_guid, hybrid_guid, ver, _session_ids);
-          break;
-        case FILESYSTEM_DISK:
-          sres = faster_t->obj.store->Recover(index_guid, hybrid_guid, ver, _session_ids);
-          break;
-      }
+      Status sres = with_store(faster_t, [&](auto* store) {
+        return store->Recover(index_guid, hybrid_guid, ver, _session_ids);
+      });
 
       uint8_t status_result = static_cast<uint8_t>(sres);
       faster_recover_result* res = (faster_recover_result*) malloc(sizeof(faster_recover_result));
@@ -690,93 +1304,277 @@ extern "C" {
     }
   }
 
-  void faster_complete_pending(faster_t* faster_t, bool b) {
+  bool faster_complete_pending(faster_t* faster_t, bool b) {
+    active_faster = faster_t;
     if (faster_t != NULL) {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          faster_t->obj.null_store->CompletePending(b);
-          break;
-        case FILESYSTEM_DISK:
-          faster_t->obj.store->CompletePending(b);
-          break;
-      }
+      return with_store(faster_t, [&](auto* store) {
+        return store->CompletePending(b);
+      });
     }
+    return true;
   }
 
   // Thread-related
 
-  const char* faster_start_session(faster_t* faster_t) {
+  bool faster_start_session(faster_t* faster_t, char* guid) {
+    active_faster = faster_t;
     if (faster_t == NULL) {
-      return NULL;
+      return false;
     } else {
-      Guid guid;
-      switch (faster_t->type) {
-        case NULL_DISK:
-          guid = faster_t->obj.null_store->StartSession();
-          break;
-        case FILESYSTEM_DISK:
-          guid = faster_t->obj.store->StartSession();
-          break;
-      }
-      char* str = new char[37];
-      std::strcpy(str, guid.ToString().c_str());
-      return str;
+      Guid id = with_store(faster_t, [](auto* store) {
+        return store->StartSession();
+      });
+      session_serials[faster_t] = 0;
+      std::strncpy(guid, id.ToString().c_str(), FASTER_GUID_SIZE - 1);
+      guid[FASTER_GUID_SIZE - 1] = '\0';
+      return true;
     }
-
   }
 
   uint64_t faster_continue_session(faster_t* faster_t, const char* token) {
+    active_faster = faster_t;
     if (faster_t == NULL) {
       return -1;
     } else {
       std::string guid_str(token);
       Guid guid = Guid::Parse(guid_str);
-      switch (faster_t->type) {
-        case NULL_DISK:
-          return faster_t->obj.null_store->ContinueSession(guid);
-        case FILESYSTEM_DISK:
-          return faster_t->obj.store->ContinueSession(guid);
-      }
+      uint64_t serial = with_store(faster_t, [&](auto* store) {
+        return store->ContinueSession(guid);
+      });
+      session_serials[faster_t] = serial;
+      return serial;
     }
   }
 
   void faster_stop_session(faster_t* faster_t) {
+    active_faster = faster_t;
     if (faster_t != NULL) {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          faster_t->obj.null_store->StopSession();
-          break;
-        case FILESYSTEM_DISK:
-          faster_t->obj.store->StopSession();
-          break;
-      }
+      with_store(faster_t, [](auto* store) {
+        store->StopSession();
+      });
+      session_serials.erase(faster_t);
     }
   }
 
   void faster_refresh_session(faster_t* faster_t) {
+    active_faster = faster_t;
     if (faster_t != NULL) {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          faster_t->obj.null_store->Refresh();
-          break;
-        case FILESYSTEM_DISK:
-          faster_t->obj.store->Refresh();
-          break;
-      }
+      with_store(faster_t, [](auto* store) {
+        store->Refresh();
+      });
     }
   }
 
   void faster_dump_distribution(faster_t* faster_t) {
     if (faster_t != NULL) {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          faster_t->obj.null_store->DumpDistribution();
-          break;
-        case FILESYSTEM_DISK:
-          faster_t->obj.store->DumpDistribution();
-          break;
+      with_store(faster_t, [](auto* store) {
+        store->DumpDistribution();
+      });
+    }
+  }
+
+  extern "C++" {
+    /// Makes the whole log up to the tail immutable and waits until no session updates records below the tail in
+    /// place anymore. Returns the tail.
+    template <class S>
+    uint64_t shift_read_only_to_tail(S* store) {
+      Address tail;
+      store->hlog.ShiftReadOnlyToTail(tail);
+      while (store->hlog.safe_read_only_address.load() < tail) {
+        store->Refresh();
+        std::this_thread::yield();
+      }
+      return tail.control();
+    }
+
+    /// Copies the live records below `until_address` to the tail of the log and then shifts the begin address of
+    /// the log to `until_address`. Only the keys of the live records are kept in memory. Their values are copied
+    /// by RMWs that read the latest record of the key, so a key that is updated or deleted while the compaction
+    /// is running keeps its new value. The RMWs continue the session of the calling thread at `serial`.
+    template <class S>
+    bool compact_store(S* store, uint64_t until_address, uint64_t serial) {
+      typedef typename S::record_t record_t;
+      Address begin = store->hlog.begin_address.load();
+      Address until{ until_address };
+      Address tail = store->hlog.GetTailAddress();
+      if (until <= begin || until > tail) {
+        return false;
+      }
+      // Records below `until` are copied rather than updated in place from here on
+      if (until > store->hlog.read_only_address.load()) {
+        tail = Address{ shift_read_only_to_tail(store) };
+      }
+
+      // The keys whose latest record below `until` is not a deletion, minus keys that are written again later on
+      std::unordered_set<std::string> live;
+      auto key_of = [](const record_t* record) {
+        const Key& key = record->key();
+        return std::string(reinterpret_cast<const char*>(key.data()), key.length());
+      };
+      ScanIterator<S> below{ &store->hlog, Buffering::DOUBLE_PAGE, begin, until, &store->disk };
+      while (const record_t* record = below.GetNext()) {
+        if (record->header.tombstone) {
+          live.erase(key_of(record));
+        } else {
+          live.insert(key_of(record));
+        }
+      }
+      ScanIterator<S> above{ &store->hlog, Buffering::DOUBLE_PAGE, until, tail, &store->disk };
+      while (const record_t* record = above.GetNext()) {
+        live.erase(key_of(record));
+      }
+
+      auto callback = [](IAsyncContext* ctxt, Status result) {
+        CallbackContext<CompactionContext> context { ctxt };
+      };
+      uint64_t copied = 0;
+      for (const auto& key : live) {
+        CompactionContext context { reinterpret_cast<const uint8_t*>(key.data()), key.size() };
+        Status result = store->Rmw(context, callback, serial, false);
+        if (result != Status::Ok && result != Status::Pending && result != Status::NotFound) {
+          return false;
+        }
+        if (++copied % 1000 == 0) {
+          store->Refresh();
+        }
       }
+      store->CompletePending(true);
+
+      auto truncate_callback = [](uint64_t offset) {};
+      auto complete_callback = []() {};
+      return store->ShiftBeginAddress(until, truncate_callback, complete_callback);
+    }
+  }
+
+  bool faster_compact(faster_t* faster_t, uint64_t until_address) {
+    active_faster = faster_t;
+    if (faster_t == NULL) {
+      return false;
     }
+    return with_store(faster_t, [&](auto* store) {
+      return compact_store(store, until_address, session_serials[faster_t]);
+    });
+  }
+
+  bool faster_shift_begin_address(faster_t* faster_t, uint64_t address) {
+    active_faster = faster_t;
+    auto truncate_callback = [](uint64_t offset) {};
+    auto complete_callback = []() {};
+    if (faster_t == NULL) {
+      return false;
+    }
+    return with_store(faster_t, [&](auto* store) {
+      return store->ShiftBeginAddress(Address{ address }, truncate_callback, complete_callback);
+    });
+  }
+
+  faster_log_addresses faster_get_log_addresses(faster_t* faster_t) {
+    faster_log_addresses addresses;
+    with_store(faster_t, [&](auto* store) {
+      addresses.begin = store->hlog.begin_address.control();
+      addresses.head = store->hlog.head_address.control();
+      addresses.read_only = store->hlog.read_only_address.control();
+      addresses.tail = store->hlog.GetTailAddress().control();
+    });
+    return addresses;
+  }
+
+  extern "C++" {
+    /// Scans the records of a store, independent of its disk type.
+    class LogScan {
+    public:
+      virtual ~LogScan() {
+      }
+      /// Returns the next record, or NULL at the end of the scanned range.
+      virtual const Record<Key, Value>* Next() = 0;
+      /// The address of the record last returned by Next().
+      virtual Address address() const = 0;
+      /// The address the scan continues at after the record last returned by Next().
+      virtual Address next_address() const = 0;
+    };
+
+    template <class S>
+    class StoreScan : public LogScan {
+    public:
+      StoreScan(S* store, Address begin, Address end)
+        : iterator_{ &store->hlog, Buffering::DOUBLE_PAGE, begin, end, &store->disk }
+        , address_{ begin }
+        , next_address_{ begin } {
+      }
+      const Record<Key, Value>* Next() override {
+        const Record<Key, Value>* record = iterator_.GetNext();
+        if (record != NULL) {
+          // The iterator skips the padding at the end of a page, so the record is located by where the iterator
+          // stopped right behind it rather than by adding up the sizes of the records before it
+          next_address_ = iterator_.current;
+          address_ = Address{ next_address_.control() - record->size() };
+        }
+        return record;
+      }
+      Address address() const override {
+        return address_;
+      }
+      Address next_address() const override {
+        return next_address_;
+      }
+
+    private:
+      ScanIterator<S> iterator_;
+      Address address_;
+      Address next_address_;
+    };
+
+    template <class S>
+    LogScan* open_scan(S* store, uint64_t begin_address, uint64_t end_address) {
+      Address begin = std::max(Address{ begin_address }, store->hlog.begin_address.load());
+      Address end = std::min(Address{ end_address }, store->hlog.GetTailAddress());
+      return new StoreScan<S>(store, begin, std::max(begin, end));
+    }
+  }
+
+  struct faster_scan_t {
+    LogScan* scan;
+  };
+
+  faster_scan_t* faster_scan_open(faster_t* faster_t, uint64_t begin_address, uint64_t end_address) {
+    active_faster = faster_t;
+    if (faster_t == NULL) {
+      return NULL;
+    }
+    faster_scan_t* res = new faster_scan_t();
+    res->scan = with_store(faster_t, [&](auto* store) {
+      return open_scan(store, begin_address, end_address);
+    });
+    return res;
+  }
+
+  bool faster_scan_next(faster_scan_t* scan, faster_scan_record* record) {
+    const Record<Key, Value>* next = scan->scan->Next();
+    if (next == NULL) {
+      return false;
+    }
+    const Key& key = next->key();
+    const Value& value = next->value();
+    record->key = key.data();
+    record->key_length = key.length();
+    record->address = scan->scan->address().control();
+    record->next_address = scan->scan->next_address().control();
+    record->tombstone = next->header.tombstone;
+    record->value = record->tombstone ? NULL : value.data();
+    record->value_length = record->tombstone ? 0 : value.length();
+    return true;
+  }
+
+  void faster_scan_close(faster_scan_t* scan) {
+    delete scan->scan;
+    delete scan;
+  }
+
+  uint64_t faster_shift_read_only_to_tail(faster_t* faster_t) {
+    active_faster = faster_t;
+    return with_store(faster_t, [](auto* store) {
+      return shift_read_only_to_tail(store);
+    });
   }
 
   bool faster_grow_index(faster_t* faster_t) {
@@ -784,13 +1582,11 @@ extern "C" {
         assert(new_size > 0);
     };
     if (faster_t != NULL) {
-      switch (faster_t->type) {
-        case NULL_DISK:
-          return faster_t->obj.null_store->GrowIndex(grow_index_callback);
-        case FILESYSTEM_DISK:
-          return faster_t->obj.store->GrowIndex(grow_index_callback);
-      }
+      return with_store(faster_t, [&](auto* store) {
+        return store->GrowIndex(grow_index_callback);
+      });
     }
+    return false;
   }
 
 } // extern "C"
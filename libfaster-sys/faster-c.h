@@ -31,8 +31,49 @@ extern "C" {
   };
   typedef enum faster_status faster_status;
 
+  enum faster_device_type {
+      // Keeps the log in memory only
+      NullDevice,
+      // Files in a storage directory, accessed through libaio
+      FileSystemDevice,
+      // Files in a storage directory, accessed through io_uring. Requires FASTER to be built with FASTER_URING.
+      IoUringDevice
+  };
+  typedef enum faster_device_type faster_device_type;
+
   typedef void (*read_callback)(void*, const uint8_t*, uint64_t, faster_status);
-  typedef uint64_t (*rmw_callback)(const uint8_t*, uint64_t, uint8_t*, uint64_t, uint8_t*);
+  typedef void* (*read_relocate_callback)(void*);
+  // Called with the value a record had before a conditional write (NULL if there was no record), and whether
+  // the new value was written
+  typedef void (*conditional_callback)(void*, const uint8_t*, uint64_t, bool, faster_status);
+  enum faster_checkpoint_kind {
+      IndexCheckpoint,
+      HybridLogCheckpoint
+  };
+  typedef enum faster_checkpoint_kind faster_checkpoint_kind;
+
+  // Called once the index is persisted, and once per session with its persistent serial number once
+  // the hybrid log is persisted
+  typedef void (*checkpoint_callback)(void*, faster_checkpoint_kind, faster_status, uint64_t);
+  // Computes the new value of a record for faster_rmw from its current value and the modification, writes it to the
+  // destination unless that is NULL, and returns its length. The current value is NULL if the key has no record yet.
+  // With the last argument set, the destination is the current value, which is only updated in place if the new value
+  // is not longer than it. Otherwise FASTER_RMW_NOT_IN_PLACE is returned without writing.
+  #define FASTER_RMW_NOT_IN_PLACE UINT64_MAX
+  // Returned by an rmw_callback or rmw_with_callback that cannot compute the value of a key without a record, which
+  // leaves the key without a record. Never returned for an in-place update, so it does not collide with
+  // FASTER_RMW_NOT_IN_PLACE.
+  #define FASTER_RMW_FAILED UINT64_MAX
+  typedef uint64_t (*rmw_callback)(const uint8_t*, uint64_t, const uint8_t*, uint64_t, uint8_t*, bool);
+  // Computes the new value of a record for faster_rmw_with from its current value, which is NULL if there is no
+  // record. Points the last argument to the encoded new value, which stays valid until the next call with the same
+  // target, and returns its length, or FASTER_RMW_FAILED.
+  typedef uint64_t (*rmw_with_callback)(void*, const uint8_t*, uint64_t, const uint8_t**);
+  // Called once a faster_rmw_with that went pending has completed, to release its target
+  typedef void (*rmw_with_done_callback)(void*, faster_status);
+  // Called with `target` once a faster_upsert, faster_rmw or faster_delete that went pending has been completed by
+  // faster_complete_pending. Operations that do not need to be notified pass NULL.
+  typedef void (*completion_callback)(void*, faster_status);
 
   typedef struct faster_checkpoint_result faster_checkpoint_result;
   struct faster_checkpoint_result {
@@ -40,6 +81,27 @@ extern "C" {
     char* token;
   };
 
+  typedef struct faster_log_addresses faster_log_addresses;
+  struct faster_log_addresses {
+    uint64_t begin;
+    uint64_t head;
+    uint64_t read_only;
+    uint64_t tail;
+  };
+
+  typedef struct faster_scan_t faster_scan_t;
+  typedef struct faster_scan_record faster_scan_record;
+  struct faster_scan_record {
+    const uint8_t* key;
+    uint64_t key_length;
+    const uint8_t* value;
+    uint64_t value_length;
+    bool tombstone;
+    uint64_t address;
+    // The address right behind the record, to resume a scan from
+    uint64_t next_address;
+  };
+
   typedef struct faster_recover_result faster_recover_result;
   struct faster_recover_result {
     uint8_t status;
@@ -49,35 +111,94 @@ extern "C" {
   };
 
   // Thread-related operations
-  const char* faster_start_session(faster_t* faster_t);
+  //
+  // faster_start_session writes the id of the new session into guid, which has to hold
+  // FASTER_GUID_SIZE bytes, as a NUL-terminated string. Returns false if faster_t is NULL.
+  #define FASTER_GUID_SIZE 37
+  bool faster_start_session(faster_t* faster_t, char* guid);
   uint64_t faster_continue_session(faster_t* faster_t, const char* token);
   void faster_stop_session(faster_t* faster_t);
   void faster_refresh_session(faster_t* faster_t);
-  void faster_complete_pending(faster_t* faster_t, bool b);
+  // Returns true once the session has no pending operations left
+  bool faster_complete_pending(faster_t* faster_t, bool b);
 
   // Checkpoint/Recover
+  void faster_set_checkpoint_callback(faster_t* faster_t, checkpoint_callback cb, void* ctx);
   faster_checkpoint_result* faster_checkpoint(faster_t* faster_t);
   faster_checkpoint_result* faster_checkpoint_index(faster_t* faster_t);
   faster_checkpoint_result* faster_checkpoint_hybrid_log(faster_t* faster_t);
   faster_recover_result* faster_recover(faster_t* faster_t, const char* index_token, const char* hybrid_log_token);
 
   // Operations
+  //
+  // Keys, values and modifications are only borrowed for the duration of a call. Operations that go
+  // pending keep their own copies.
   faster_t* faster_open(const uint64_t table_size, const uint64_t log_size, bool pre_allocate_log);
   faster_t* faster_open_with_disk(const uint64_t table_size, const uint64_t log_size, const char* storage,
                                   double log_mutable_fraction, bool pre_allocate_log);
+  // Returns NULL if the device is not available or the segment size is not a power of two between 2^24 and 2^34
+  faster_t* faster_open_with_device(const uint64_t table_size, const uint64_t log_size, faster_device_type device,
+                                    const char* storage, uint64_t segment_size, double log_mutable_fraction,
+                                    bool pre_allocate_log);
   uint8_t faster_upsert(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
-                        uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number);
-  uint8_t faster_rmw(faster_t* faster_t, const uint8_t* key, const uint64_t key_length, uint8_t* modification,
-                     const uint64_t length, const uint64_t monotonic_serial_number, rmw_callback cb);
+                        const uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number,
+                        completion_callback done, void* target);
+  uint8_t faster_rmw(faster_t* faster_t, const uint8_t* key, const uint64_t key_length, const uint8_t* modification,
+                     const uint64_t length, const uint64_t monotonic_serial_number, rmw_callback cb,
+                     completion_callback done, void* target);
+  // Like faster_rmw, but the new value is computed by `cb` with `target` instead of from a modification. `relocate`
+  // is called like for faster_read_with when the operation goes pending.
+  uint8_t faster_rmw_with(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                          const uint64_t monotonic_serial_number, rmw_with_callback cb,
+                          read_relocate_callback relocate, rmw_with_done_callback done, void* target);
   uint8_t faster_read(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
                        const uint64_t monotonic_serial_number, read_callback cb, void* target);
+  // Like faster_read, but `relocate` is called with `target` when the read goes pending and returns the
+  // target to use for the deferred callback. This allows `target` to live on the caller's stack.
+  uint8_t faster_read_with(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                           const uint64_t monotonic_serial_number, read_callback cb,
+                           read_relocate_callback relocate, void* target);
+  // Conditional writes, which report their outcome to `cb` like faster_read_with: before they return if they
+  // complete synchronously, and otherwise once faster_complete_pending completes them.
+  // faster_compare_and_swap writes `value` if the current value is equal to `expected` (which must not be NULL)
+  // byte for byte, and returns NotFound without writing if there is no record. faster_insert_if_absent writes `value` only if there is no
+  // record. Both are RMWs that cannot be aborted once FASTER copies a record from below the read-only address, so a
+  // write whose condition fails on such a record still appends an unchanged copy of it to the tail.
+  uint8_t faster_compare_and_swap(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                                  const uint8_t* expected, uint64_t expected_length, const uint8_t* value,
+                                  uint64_t value_length, const uint64_t monotonic_serial_number,
+                                  conditional_callback cb, read_relocate_callback relocate, void* target);
+  uint8_t faster_insert_if_absent(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
+                                  const uint8_t* value, uint64_t value_length, const uint64_t monotonic_serial_number,
+                                  conditional_callback cb, read_relocate_callback relocate, void* target);
   uint8_t faster_delete(faster_t* faster_t, const uint8_t* key, const uint64_t key_length,
-                        const uint64_t monotonic_serial_number);
+                        const uint64_t monotonic_serial_number, completion_callback done, void* target);
   void faster_destroy(faster_t* faster_t);
   bool faster_grow_index(faster_t* faster_t);
 
+  // Log compaction
+  //
+  // Both run on behalf of the session of the calling thread. The log is truncated once every session has
+  // refreshed. faster_compact copies the latest value of each live key below `until_address` to the tail, skipping
+  // keys that have been updated or deleted since, and makes the log up to the tail read-only if `until_address`
+  // lies in the mutable region.
+  bool faster_compact(faster_t* faster_t, uint64_t until_address);
+  bool faster_shift_begin_address(faster_t* faster_t, uint64_t address);
+
+  // Log scan
+  //
+  // Scans the records in [begin_address, end_address) in log order, including superseded versions and tombstones.
+  // The key and value of a record are valid until the next call to faster_scan_next. Scans are driven by the
+  // session of the calling thread.
+  faster_scan_t* faster_scan_open(faster_t* faster_t, uint64_t begin_address, uint64_t end_address);
+  bool faster_scan_next(faster_scan_t* scan, faster_scan_record* record);
+  void faster_scan_close(faster_scan_t* scan);
+  // Makes the log up to the tail read-only, so that records below the returned tail are no longer updated in place
+  uint64_t faster_shift_read_only_to_tail(faster_t* faster_t);
+
   // Statistics
   uint64_t faster_size(faster_t* faster_t);
+  faster_log_addresses faster_get_log_addresses(faster_t* faster_t);
   void faster_dump_distribution(faster_t* faster_t);
 
 #ifdef __cplusplus
@@ -0,0 +1,98 @@
+use std::sync::{Condvar, Mutex};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MIN_DELAY: Duration = Duration::from_micros(50);
+const MAX_DELAY: Duration = Duration::from_millis(10);
+
+// Wakers that are due at a deadline, shared by all async sessions and served by one background
+// thread that is started by the first wait
+struct Timers {
+    started: bool,
+    pending: Vec<(Instant, Waker)>,
+}
+
+static TIMERS: Mutex<Timers> = Mutex::new(Timers {
+    started: false,
+    pending: Vec::new(),
+});
+static TIMER_ADDED: Condvar = Condvar::new();
+
+/// Paces a future that waits for FASTER to complete a pending operation. FASTER only runs the
+/// callback of the operation when the session completes its pending operations, so the future
+/// has to be polled again to drive them. It is woken after a delay that doubles with every
+/// wait, from 50µs up to 10ms, instead of being polled in a busy loop.
+pub(crate) struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new() -> Backoff {
+        Backoff { delay: MIN_DELAY }
+    }
+
+    /// Wakes `waker` once the current delay has passed. With the `tokio` feature, a future that
+    /// is polled by a tokio runtime is timed by the runtime, which has to have its timer enabled.
+    pub(crate) fn wake_later(&mut self, waker: &Waker) {
+        let deadline = Instant::now() + self.delay;
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+        #[cfg(feature = "tokio")]
+        {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let waker = waker.clone();
+                runtime.spawn(async move {
+                    tokio::time::sleep_until(deadline.into()).await;
+                    waker.wake();
+                });
+                return;
+            }
+        }
+        wake_at(deadline, waker.clone());
+    }
+}
+
+fn wake_at(deadline: Instant, waker: Waker) {
+    let mut timers = TIMERS.lock().unwrap();
+    if !timers.started {
+        let spawned = thread::Builder::new()
+            .name(String::from("faster-backoff"))
+            .spawn(run_timers);
+        if spawned.is_err() {
+            // Without timers the future is polled again right away
+            drop(timers);
+            waker.wake();
+            return;
+        }
+        timers.started = true;
+    }
+    timers.pending.push((deadline, waker));
+    TIMER_ADDED.notify_one();
+}
+
+fn run_timers() {
+    let mut timers = TIMERS.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < timers.pending.len() {
+            if timers.pending[i].0 <= now {
+                due.push(timers.pending.swap_remove(i).1);
+            } else {
+                i += 1;
+            }
+        }
+        if !due.is_empty() {
+            // Executors may take locks of their own when woken
+            drop(timers);
+            due.into_iter().for_each(Waker::wake);
+            timers = TIMERS.lock().unwrap();
+            continue;
+        }
+        timers = match timers.pending.iter().map(|(deadline, _)| *deadline).min() {
+            Some(deadline) => TIMER_ADDED.wait_timeout(timers, deadline - now).unwrap().0,
+            None => TIMER_ADDED.wait(timers).unwrap(),
+        };
+    }
+}
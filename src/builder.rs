@@ -1,12 +1,59 @@
-use crate::{FasterError, FasterKv};
+use crate::checkpoint::{checkpoint_callback, CheckpointTracker, RetentionPolicy};
+use crate::scheduler::{CheckpointScheduler, SchedulerHandle};
+use crate::{Bincode, FasterError, FasterKv};
 use std::ffi::CString;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Segment size of disk-backed stores unless configured otherwise, 1 GiB.
+pub const DEFAULT_SEGMENT_SIZE: u64 = 1 << 30;
+const MIN_SEGMENT_SIZE: u64 = 1 << 24;
+const MAX_SEGMENT_SIZE: u64 = 1 << 34;
+
+/// The device a store keeps its log on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Device<'a> {
+    /// Keeps the log in memory only. Records that no longer fit into the in-memory log are lost,
+    /// and the store cannot be checkpointed.
+    Null,
+    /// Stores the log in files of `segment_size` bytes under `path`, accessed through libaio.
+    FileSystem { path: &'a str, segment_size: u64 },
+    /// Like `FileSystem`, but accessed through io_uring. Requires the `io_uring` feature.
+    IoUring { path: &'a str, segment_size: u64 },
+}
+
+impl<'a> Device<'a> {
+    fn path(&self) -> Option<&'a str> {
+        match *self {
+            Device::Null => None,
+            Device::FileSystem { path, .. } | Device::IoUring { path, .. } => Some(path),
+        }
+    }
+}
+
+/// The configuration a store was built with, see `FasterKv::config`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FasterKvConfig {
+    pub table_size: u64,
+    pub log_size: u64,
+    /// The directory of a disk-backed store.
+    pub storage_dir: Option<String>,
+    /// The size of the segment files of a disk-backed store.
+    pub segment_size: Option<u64>,
+    pub log_mutable_fraction: f64,
+    pub pre_allocate_log: bool,
+    pub checkpoint_retention: RetentionPolicy,
+}
 
 pub struct FasterKvBuilder<'a> {
     table_size: u64,
     log_size: u64,
-    storage: Option<&'a str>,
+    device: Device<'a>,
+    segment_size: Option<u64>,
     log_mutable_fraction: f64,
     pre_allocate_log: bool,
+    checkpoint_retention: RetentionPolicy,
+    checkpoint_scheduler: Option<CheckpointScheduler>,
 }
 
 impl<'a> FasterKvBuilder<'a> {
@@ -14,14 +61,33 @@ impl<'a> FasterKvBuilder<'a> {
         FasterKvBuilder {
             table_size,
             log_size,
-            storage: None,
+            device: Device::Null,
+            segment_size: None,
             log_mutable_fraction: 0.9,
             pre_allocate_log: false,
+            checkpoint_retention: RetentionPolicy::keep_all(),
+            checkpoint_scheduler: None,
         }
     }
 
+    /// Stores the log under `path`, in segments of `DEFAULT_SEGMENT_SIZE` bytes unless set by
+    /// `with_segment_size`.
     pub fn with_disk(&mut self, path: &'a str) -> &mut FasterKvBuilder<'a> {
-        self.storage = Some(path);
+        self.with_device(Device::FileSystem {
+            path,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+        })
+    }
+
+    pub fn with_device(&mut self, device: Device<'a>) -> &mut FasterKvBuilder<'a> {
+        self.device = device;
+        self
+    }
+
+    /// Sets the size of the segment files of a disk-backed store, overriding the segment size
+    /// of its device. Must be a power of two between 16 MiB and 16 GiB.
+    pub fn with_segment_size(&mut self, bytes: u64) -> &mut FasterKvBuilder<'a> {
+        self.segment_size = Some(bytes);
         self
     }
 
@@ -35,41 +101,126 @@ impl<'a> FasterKvBuilder<'a> {
         self
     }
 
+    /// Prunes the checkpoints in the storage directory that `policy` no longer keeps whenever a
+    /// new checkpoint is started, see `FasterKv::prune_checkpoints`.
+    pub fn with_checkpoint_retention(&mut self, policy: RetentionPolicy) -> &mut FasterKvBuilder<'a> {
+        self.checkpoint_retention = policy;
+        self
+    }
+
+    /// Checkpoints the store in the background as configured by `scheduler`. Requires a
+    /// disk-backed device.
+    pub fn with_checkpoint_scheduler(&mut self, scheduler: CheckpointScheduler) -> &mut FasterKvBuilder<'a> {
+        self.checkpoint_scheduler = Some(scheduler);
+        self
+    }
+
     pub fn build(&self) -> Result<FasterKv, FasterError<'static>> {
+        self.build_with_codec::<Bincode>()
+    }
+
+    /// Builds a store that encodes its keys and values with the codec `C`.
+    pub fn build_with_codec<C>(&self) -> Result<FasterKv<C>, FasterError<'static>> {
         if !(self.log_mutable_fraction > 0.0 && self.log_mutable_fraction <= 1.0) {
             return Err(FasterError::BuilderError(
                 "Log mutable fraction must be between 0 and 1",
             ));
         }
-        unsafe {
-            let mut storage_dir = None;
-            let faster_t = match self.storage {
-                None => ffi::faster_open(self.table_size, self.log_size, self.pre_allocate_log),
-                Some(path) => {
-                    let storage_str = CString::new(path).unwrap();
-                    let ptr_raw = storage_str.into_raw();
-                    let ft = ffi::faster_open_with_disk(
-                        self.table_size,
-                        self.log_size,
-                        ptr_raw,
-                        self.log_mutable_fraction,
-                        self.pre_allocate_log,
-                    );
-                    storage_dir = CString::from_raw(ptr_raw).into_string().ok();
-                    ft
+        let (device_type, device_segment_size) = match self.device {
+            Device::Null => (ffi::faster_device_type_NullDevice, None),
+            Device::FileSystem { segment_size, .. } => {
+                (ffi::faster_device_type_FileSystemDevice, Some(segment_size))
+            }
+            Device::IoUring { segment_size, .. } => {
+                if !cfg!(feature = "io_uring") {
+                    return Err(FasterError::BuilderError(
+                        "io_uring devices require the io_uring feature",
+                    ));
                 }
-            };
-            Ok(FasterKv {
+                (ffi::faster_device_type_IoUringDevice, Some(segment_size))
+            }
+        };
+        let segment_size = match (device_segment_size, self.segment_size) {
+            (None, Some(_)) => {
+                return Err(FasterError::BuilderError(
+                    "Segment size requires a disk-backed device",
+                ))
+            }
+            (device_segment_size, segment_size) => segment_size.or(device_segment_size),
+        };
+        if let Some(segment_size) = segment_size {
+            if !segment_size.is_power_of_two() {
+                return Err(FasterError::BuilderError(
+                    "Segment size must be a power of two",
+                ));
+            }
+            if !(MIN_SEGMENT_SIZE..=MAX_SEGMENT_SIZE).contains(&segment_size) {
+                return Err(FasterError::BuilderError(
+                    "Segment size must be between 16 MiB and 16 GiB",
+                ));
+            }
+        }
+        if let Some(scheduler) = &self.checkpoint_scheduler {
+            if self.device.path().is_none() {
+                return Err(FasterError::BuilderError(
+                    "Checkpoint scheduler requires a disk-backed device",
+                ));
+            }
+            if !scheduler.is_valid() {
+                return Err(FasterError::BuilderError(
+                    "Checkpoint scheduler needs a positive interval or operation count",
+                ));
+            }
+        }
+        let config = FasterKvConfig {
+            table_size: self.table_size,
+            log_size: self.log_size,
+            storage_dir: self.device.path().map(String::from),
+            segment_size,
+            log_mutable_fraction: self.log_mutable_fraction,
+            pre_allocate_log: self.pre_allocate_log,
+            checkpoint_retention: self.checkpoint_retention,
+        };
+        let storage_str = CString::new(self.device.path().unwrap_or("")).unwrap();
+        unsafe {
+            let faster_t = ffi::faster_open_with_device(
+                self.table_size,
+                self.log_size,
+                device_type,
+                storage_str.as_ptr(),
+                segment_size.unwrap_or(0),
+                self.log_mutable_fraction,
+                self.pre_allocate_log,
+            );
+            if faster_t.is_null() {
+                return Err(FasterError::BuilderError("Device is not supported"));
+            }
+            let checkpoints = Arc::new(CheckpointTracker::new());
+            ffi::faster_set_checkpoint_callback(
+                faster_t,
+                Some(checkpoint_callback),
+                Arc::as_ptr(&checkpoints) as *mut libc::c_void,
+            );
+            let mut store = FasterKv {
                 faster_t,
-                storage_dir,
-            })
+                config,
+                checkpoints,
+                scheduler: None,
+                codec: PhantomData,
+            };
+            if let Some(scheduler) = &self.checkpoint_scheduler {
+                store.scheduler = Some(SchedulerHandle::spawn(scheduler.clone(), &store)?);
+            }
+            Ok(store)
         }
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::FasterKvBuilder;
+    use super::{Device, FasterKvBuilder};
+    use crate::{CheckpointScheduler, FasterError, FasterKv};
+    use std::time::Duration;
     use tempfile::TempDir;
     #[test]
     fn can_build_with_disk() {
@@ -81,7 +232,80 @@ pub mod tests {
             .set_pre_allocate_log(true)
             .with_log_mutable_fraction(0.8);
         let kv = builder.build().unwrap();
-        let storage = &kv.storage_dir;
+        let storage = &kv.config().storage_dir;
         assert_eq!(storage.as_ref().unwrap(), dir_str);
     }
+
+    #[test]
+    fn can_build_with_segment_size() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_str().unwrap();
+        let mut builder = FasterKvBuilder::new(1 << 15, 1 << 26);
+        builder.with_device(Device::FileSystem {
+            path: dir_str,
+            segment_size: 1 << 24,
+        });
+        let kv = builder.build().unwrap();
+        assert_eq!(kv.config().storage_dir.as_ref().unwrap(), dir_str);
+        assert_eq!(kv.config().segment_size, Some(1 << 24));
+    }
+
+    #[test]
+    fn segment_size_overrides_device() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_str().unwrap();
+        let mut builder = FasterKvBuilder::new(1 << 15, 1 << 26);
+        builder.with_segment_size(1 << 25).with_disk(dir_str);
+        let kv = builder.build().unwrap();
+        assert_eq!(kv.config().segment_size, Some(1 << 25));
+    }
+
+    #[test]
+    fn segment_size_requires_disk() {
+        let mut builder = FasterKvBuilder::new(1 << 15, 1 << 26);
+        builder.with_segment_size(1 << 25);
+        assert!(builder.build().is_err());
+        assert_eq!(FasterKv::default().config().segment_size, None);
+    }
+
+    #[test]
+    fn invalid_segment_size_errors() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_str().unwrap();
+        for segment_size in [0, 3 << 24, 1 << 23, 1 << 35].iter() {
+            let mut builder = FasterKvBuilder::new(1 << 15, 1 << 26);
+            builder.with_device(Device::FileSystem {
+                path: dir_str,
+                segment_size: *segment_size,
+            });
+            match builder.build() {
+                Err(FasterError::BuilderError(_)) => {}
+                _ => panic!("Segment size {} should be rejected", segment_size),
+            }
+        }
+    }
+
+    #[test]
+    fn checkpoint_scheduler_requires_disk() {
+        let mut builder = FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024);
+        builder.with_checkpoint_scheduler(CheckpointScheduler::every(Duration::from_secs(1)));
+        match builder.build() {
+            Err(FasterError::BuilderError(_)) => {}
+            _ => panic!("Should give BuilderError"),
+        }
+    }
+
+    #[test]
+    fn checkpoint_scheduler_rejects_zero_operations() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_str().unwrap();
+        let mut builder = FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024);
+        builder
+            .with_disk(dir_str)
+            .with_checkpoint_scheduler(CheckpointScheduler::after_operations(0));
+        match builder.build() {
+            Err(FasterError::BuilderError(_)) => {}
+            _ => panic!("Should give BuilderError"),
+        }
+    }
 }
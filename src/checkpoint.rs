@@ -0,0 +1,701 @@
+extern crate libc;
+extern crate libfaster_sys as ffi;
+
+use crate::builder::FasterKvConfig;
+use crate::guid::{CheckpointToken, SessionId};
+use crate::session::{active_session_ids, Session};
+use crate::{FasterError, Status};
+
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::fs;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Poll, Waker};
+use std::time::{Duration, SystemTime};
+
+/// A checkpoint started by `FasterKv::checkpoint` and its variants.
+///
+/// FASTER persists the checkpoint in the background, so it is not durable yet when it is
+/// returned. `wait` and `persisted` report once it is, together with the serial number up to
+/// which the operations of each session are durable.
+pub struct CheckPoint {
+    pub checked: bool,
+    /// The token of the checkpoint, if FASTER started it.
+    pub token: Option<CheckpointToken>,
+    progress: Option<Arc<Progress>>,
+}
+
+impl CheckPoint {
+    pub(crate) fn new(checked: bool, token: Option<CheckpointToken>, progress: Option<Arc<Progress>>) -> CheckPoint {
+        CheckPoint {
+            checked,
+            token,
+            progress,
+        }
+    }
+
+    /// Whether the index and hybrid log of this checkpoint have been persisted.
+    pub fn is_persisted(&self) -> bool {
+        self.progress.as_ref().is_some_and(|progress| progress.result().is_some())
+    }
+
+    /// Blocks until the checkpoint has been persisted, completing pending operations and
+    /// refreshing `session` in the meantime. The hybrid log is only persisted once every
+    /// session of the store has reached the checkpoint, so other sessions have to keep
+    /// refreshing as well.
+    ///
+    /// Returns `CheckpointError` if FASTER did not start the checkpoint.
+    pub fn wait<'s, C>(&self, session: &'s Session<C>) -> Result<PersistedCheckpoint, FasterError<'s>> {
+        let progress = match &self.progress {
+            Some(progress) => progress,
+            None => return Err(FasterError::CheckpointError),
+        };
+        wait_for(progress, || {
+            session.complete_pending(false)?;
+            session.refresh();
+            Ok(())
+        })
+    }
+
+    /// Blocks until the checkpoint has been persisted by the sessions of the store, without
+    /// driving any of them. Gives up with `None` once `cancelled` returns true.
+    pub(crate) fn wait_unless<F>(&self, cancelled: F) -> Option<Result<PersistedCheckpoint, FasterError<'static>>>
+    where
+        F: Fn() -> bool,
+    {
+        let progress = match &self.progress {
+            Some(progress) => progress,
+            None => return Some(Err(FasterError::CheckpointError)),
+        };
+        let result = wait_for(progress, || match cancelled() {
+            true => Err(FasterError::CheckpointError),
+            false => Ok(()),
+        });
+        progress.result().map(|_| result)
+    }
+
+    /// Resolves once the checkpoint has been persisted. Unlike `wait`, this does not drive any
+    /// session, which has to be done by the sessions of the store, e.g. through `refresh` or
+    /// the operations of an `AsyncSession`.
+    pub fn persisted(&self) -> impl Future<Output = Result<PersistedCheckpoint, FasterError<'static>>> {
+        let progress = self.progress.clone();
+        poll_fn(move |cx| {
+            let progress = match &progress {
+                Some(progress) => progress,
+                None => return Poll::Ready(Err(FasterError::CheckpointError)),
+            };
+            if progress.register(cx.waker()) {
+                return Poll::Pending;
+            }
+            Poll::Ready(progress.result().unwrap())
+        })
+    }
+}
+
+/// The persistent serial number of a session in a checkpoint. All operations of the session up to
+/// and including this serial number are durable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionCheckpoint {
+    pub session_id: SessionId,
+    pub persistent_serial: u64,
+}
+
+/// A checkpoint whose index and hybrid log (as requested) have been persisted.
+#[derive(Clone, Debug)]
+pub struct PersistedCheckpoint {
+    pub token: CheckpointToken,
+    /// The sessions that were active when the checkpoint was taken. Empty for index checkpoints.
+    pub sessions: Vec<SessionCheckpoint>,
+}
+
+/// What a checkpoint started by the store persists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// The index and the hybrid log, see `FasterKv::checkpoint`.
+    Full,
+    /// The index only, see `FasterKv::checkpoint_index`.
+    Index,
+    /// The hybrid log only, see `FasterKv::checkpoint_hybrid_log`.
+    HybridLog,
+}
+
+/// Starts a checkpoint of `faster_t`, the store that was built with `config` and whose
+/// checkpoints are tracked by `tracker`.
+pub(crate) fn start_checkpoint(
+    faster_t: *mut ffi::faster_t,
+    config: &FasterKvConfig,
+    tracker: &CheckpointTracker,
+    mode: CheckpointMode,
+) -> Result<CheckPoint, FasterError<'static>> {
+    if config.storage_dir.is_none() {
+        return Err(FasterError::InvalidType);
+    }
+    let (index, hybrid_log, start): (_, _, unsafe extern "C" fn(*mut ffi::faster_t) -> _) = match mode {
+        CheckpointMode::Full => (true, true, ffi::faster_checkpoint),
+        CheckpointMode::Index => (true, false, ffi::faster_checkpoint_index),
+        CheckpointMode::HybridLog => (false, true, ffi::faster_checkpoint_hybrid_log),
+    };
+
+    let progress = tracker.begin(index, hybrid_log);
+    if progress.is_none() {
+        // FASTER rejects a checkpoint while the previous one is still being persisted
+        return Ok(CheckPoint::new(false, None, None));
+    }
+    // Pruning is best effort, a checkpoint directory that cannot be removed now will be
+    // retried with the next checkpoint
+    let _ = prune_checkpoints(config, tracker);
+    let result = unsafe { start(faster_t) };
+    match result.is_null() {
+        true => Err(FasterError::CheckpointError),
+        false => {
+            // The shim allocates the result and its token with malloc
+            let (checked, token_ptr) = unsafe { ((*result).checked, (*result).token) };
+            let token = unsafe { CStr::from_ptr(token_ptr) }
+                .to_str()
+                .ok()
+                .and_then(|token| token.parse::<CheckpointToken>().ok())
+                .filter(|_| checked);
+            unsafe {
+                libc::free(token_ptr as *mut libc::c_void);
+                libc::free(result as *mut libc::c_void);
+            }
+
+            let checked = token.is_some();
+            let progress = progress.filter(|progress| {
+                tracker.started(progress, token);
+                checked
+            });
+            Ok(CheckPoint::new(checked, token, progress))
+        }
+    }
+}
+
+/// Removes the checkpoints that the retention policy of `config` no longer keeps, except for
+/// the one that is being persisted. Returns the removed checkpoints.
+pub(crate) fn prune_checkpoints(
+    config: &FasterKvConfig,
+    tracker: &CheckpointTracker,
+) -> Result<Vec<CheckpointInfo>, FasterError<'static>> {
+    let storage_dir = match &config.storage_dir {
+        None => return Err(FasterError::InvalidType),
+        Some(dir) => dir,
+    };
+    let checkpoints = list_checkpoints(storage_dir)?;
+    let running = tracker.running();
+    let mut removed = Vec::new();
+    for expired in config.checkpoint_retention.expired(&checkpoints, SystemTime::now()) {
+        if running == Some(expired.token) {
+            continue;
+        }
+        remove_checkpoint(storage_dir, expired.token, expired.kind)?;
+        removed.push(expired.clone());
+    }
+    Ok(removed)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CheckpointKind {
+    Index,
+    HybridLog,
+}
+
+/// A checkpoint found in the storage directory of a store, see `FasterKv::list_checkpoints`.
+/// A full checkpoint is listed twice, once for its index and once for its hybrid log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckpointInfo {
+    pub token: CheckpointToken,
+    pub kind: CheckpointKind,
+    /// The version of the store the checkpoint was taken at.
+    pub version: u32,
+    /// When the checkpoint metadata was written.
+    pub timestamp: SystemTime,
+    // The start address of an index checkpoint, or the final address of a hybrid log checkpoint
+    address: u64,
+}
+
+/// Reads the metadata of the checkpoints under `storage_dir`, oldest first. Checkpoints without
+/// complete metadata, e.g. because they were interrupted, are left out.
+pub(crate) fn list_checkpoints(storage_dir: &str) -> io::Result<Vec<CheckpointInfo>> {
+    let mut checkpoints = Vec::new();
+    let kinds = [
+        ("index-checkpoints", CheckpointKind::Index),
+        ("cpr-checkpoints", CheckpointKind::HybridLog),
+    ];
+    for (dir, kind) in kinds.iter() {
+        let dir = Path::new(storage_dir).join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            // Skips anything in there that FASTER did not create
+            let token = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(token) => token,
+                None => continue,
+            };
+            if let Some(checkpoint) = read_info(&entry.path().join("info.dat"), token, *kind)? {
+                checkpoints.push(checkpoint);
+            }
+        }
+    }
+    checkpoints.sort_by_key(|checkpoint| (checkpoint.timestamp, checkpoint.version));
+    Ok(checkpoints)
+}
+
+// Offsets into FASTER's IndexMetadata and LogMetadata, which are written to info.dat as they
+// are laid out in memory
+const INDEX_VERSION_OFFSET: usize = 0;
+const INDEX_START_ADDRESS_OFFSET: usize = 48;
+const INDEX_METADATA_SIZE: usize = 56;
+const LOG_VERSION_OFFSET: usize = 4;
+const LOG_FINAL_ADDRESS_OFFSET: usize = 24;
+const LOG_METADATA_MIN_SIZE: usize = 32;
+
+fn read_info(path: &Path, token: CheckpointToken, kind: CheckpointKind) -> io::Result<Option<CheckpointInfo>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let (version_offset, address_offset, min_size) = match kind {
+        CheckpointKind::Index => (INDEX_VERSION_OFFSET, INDEX_START_ADDRESS_OFFSET, INDEX_METADATA_SIZE),
+        CheckpointKind::HybridLog => (LOG_VERSION_OFFSET, LOG_FINAL_ADDRESS_OFFSET, LOG_METADATA_MIN_SIZE),
+    };
+    if bytes.len() < min_size {
+        return Ok(None);
+    }
+    let mut version = [0u8; 4];
+    version.copy_from_slice(&bytes[version_offset..version_offset + 4]);
+    let mut address = [0u8; 8];
+    address.copy_from_slice(&bytes[address_offset..address_offset + 8]);
+    Ok(Some(CheckpointInfo {
+        token,
+        kind,
+        version: u32::from_ne_bytes(version),
+        timestamp: fs::metadata(path)?.modified()?,
+        address: u64::from_ne_bytes(address),
+    }))
+}
+
+/// Picks the newest hybrid log checkpoint that can be recovered together with an index
+/// checkpoint, which has to have started before the hybrid log checkpoint finished. Returns the
+/// index and hybrid log tokens.
+pub(crate) fn latest_recoverable(checkpoints: &[CheckpointInfo]) -> Option<(CheckpointToken, CheckpointToken)> {
+    recoverable_pair(checkpoints).map(|(index, log)| (index.token, log.token))
+}
+
+fn recoverable_pair(checkpoints: &[CheckpointInfo]) -> Option<(&CheckpointInfo, &CheckpointInfo)> {
+    checkpoints
+        .iter()
+        .rev()
+        .filter(|c| c.kind == CheckpointKind::HybridLog)
+        .find_map(|log| recovery_index(checkpoints, log).map(|index| (index, log)))
+}
+
+// The index checkpoint that the hybrid log checkpoint `log` is recovered with
+fn recovery_index<'c>(checkpoints: &'c [CheckpointInfo], log: &CheckpointInfo) -> Option<&'c CheckpointInfo> {
+    let indexes = || checkpoints.iter().filter(|c| c.kind == CheckpointKind::Index);
+    // A full checkpoint shares its token between the index and the hybrid log
+    indexes()
+        .find(|index| index.token == log.token)
+        .or_else(|| indexes().rev().find(|index| index.address <= log.address))
+}
+
+/// Which checkpoints a store keeps in its storage directory, see
+/// `FasterKvBuilder::with_checkpoint_retention`.
+///
+/// A checkpoint is kept if it is one of the newest `keep_last` checkpoints of its kind, or
+/// younger than `keep_newer_than`. The index checkpoints that kept hybrid log checkpoints are
+/// recovered with, and the checkpoints `FasterKv::recover_latest` would recover from, are always
+/// kept. Without any limit, which is the default, every checkpoint is kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_newer_than: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub fn keep_all() -> RetentionPolicy {
+        RetentionPolicy::default()
+    }
+
+    pub fn keep_last(count: usize) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: Some(count),
+            keep_newer_than: None,
+        }
+    }
+
+    pub fn keep_newer_than(age: Duration) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last: None,
+            keep_newer_than: Some(age),
+        }
+    }
+
+    /// The checkpoints, as listed by `list_checkpoints`, that this policy no longer keeps.
+    pub(crate) fn expired<'c>(&self, checkpoints: &'c [CheckpointInfo], now: SystemTime) -> Vec<&'c CheckpointInfo> {
+        if self.keep_last.is_none() && self.keep_newer_than.is_none() {
+            return Vec::new();
+        }
+        let mut kept = HashSet::new();
+        for kind in [CheckpointKind::Index, CheckpointKind::HybridLog].iter() {
+            let newest_first = checkpoints.iter().rev().filter(|c| c.kind == *kind);
+            for (newer, checkpoint) in newest_first.enumerate() {
+                let recent = self.keep_last.is_some_and(|count| newer < count);
+                let young = self.keep_newer_than.is_some_and(|age| {
+                    // A timestamp in the future counts as young
+                    now.duration_since(checkpoint.timestamp).map_or(true, |elapsed| elapsed < age)
+                });
+                if recent || young {
+                    kept.insert((checkpoint.token, checkpoint.kind));
+                }
+            }
+        }
+        let recovery_indexes: Vec<&CheckpointInfo> = checkpoints
+            .iter()
+            .filter(|c| c.kind == CheckpointKind::HybridLog && kept.contains(&(c.token, c.kind)))
+            .filter_map(|log| recovery_index(checkpoints, log))
+            .collect();
+        for index in recovery_indexes {
+            kept.insert((index.token, index.kind));
+        }
+        if let Some((index, log)) = recoverable_pair(checkpoints) {
+            kept.insert((index.token, index.kind));
+            kept.insert((log.token, log.kind));
+        }
+        checkpoints
+            .iter()
+            .filter(|c| !kept.contains(&(c.token, c.kind)))
+            .collect()
+    }
+}
+
+/// Removes the files of a checkpoint from `storage_dir`. Returns whether there were any.
+pub(crate) fn remove_checkpoint(storage_dir: &str, token: CheckpointToken, kind: CheckpointKind) -> io::Result<bool> {
+    let dir = match kind {
+        CheckpointKind::Index => "index-checkpoints",
+        CheckpointKind::HybridLog => "cpr-checkpoints",
+    };
+    let path = Path::new(storage_dir).join(dir).join(token.to_string());
+    match fs::remove_dir_all(path) {
+        Ok(()) => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Progress of a single checkpoint, updated from FASTER's persistence callbacks.
+pub(crate) struct Progress {
+    state: Mutex<ProgressState>,
+    persisted: Condvar,
+}
+
+struct ProgressState {
+    // Set once FASTER has started the checkpoint
+    token: Option<CheckpointToken>,
+    index_pending: bool,
+    sessions_pending: HashSet<SessionId>,
+    sessions: Vec<SessionCheckpoint>,
+    status: Status,
+    complete: bool,
+    wakers: Vec<Waker>,
+}
+
+impl Progress {
+    fn new(index: bool, sessions: HashSet<SessionId>) -> Progress {
+        // A hybrid log checkpoint without sessions has nothing to wait for
+        let complete = !index && sessions.is_empty();
+        Progress {
+            state: Mutex::new(ProgressState {
+                token: None,
+                index_pending: index,
+                sessions_pending: sessions,
+                sessions: Vec::new(),
+                status: Status::Ok,
+                complete,
+                wakers: Vec::new(),
+            }),
+            persisted: Condvar::new(),
+        }
+    }
+
+    /// The outcome of the checkpoint, or `None` while it is still being persisted.
+    pub(crate) fn result(&self) -> Option<Result<PersistedCheckpoint, FasterError<'static>>> {
+        let state = self.state.lock().unwrap();
+        if !state.complete {
+            return None;
+        }
+        let token = state.token?;
+        Some(state.status.into_result().map(|_| PersistedCheckpoint {
+            token,
+            sessions: state.sessions.clone(),
+        }))
+    }
+
+    /// Registers `waker` to be woken once the checkpoint is persisted. Returns false if it
+    /// already is.
+    pub(crate) fn register(&self, waker: &Waker) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.complete {
+            return false;
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(waker)) {
+            state.wakers.push(waker.clone());
+        }
+        true
+    }
+
+    fn update<F: FnOnce(&mut ProgressState)>(&self, f: F) {
+        let mut state = self.state.lock().unwrap();
+        if state.complete {
+            return;
+        }
+        f(&mut state);
+        if !state.index_pending && state.sessions_pending.is_empty() {
+            state.complete = true;
+            self.persisted.notify_all();
+            for waker in state.wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Keeps track of the sessions of a store and of its running checkpoint. FASTER runs at most one
+/// checkpoint at a time.
+pub(crate) struct CheckpointTracker {
+    sessions: Mutex<HashSet<SessionId>>,
+    current: Mutex<Option<Arc<Progress>>>,
+}
+
+impl CheckpointTracker {
+    pub(crate) fn new() -> CheckpointTracker {
+        CheckpointTracker {
+            sessions: Mutex::new(HashSet::new()),
+            current: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn register_session(&self, id: SessionId) {
+        self.sessions.lock().unwrap().insert(id);
+    }
+
+    pub(crate) fn has_session(&self, id: SessionId) -> bool {
+        self.sessions.lock().unwrap().contains(&id)
+    }
+
+    /// A session that stops before persisting its part of a checkpoint is not waited for.
+    pub(crate) fn unregister_session(&self, id: SessionId) {
+        self.sessions.lock().unwrap().remove(&id);
+        if let Some(progress) = self.current() {
+            progress.update(|state| {
+                state.sessions_pending.remove(&id);
+            });
+        }
+    }
+
+    /// Starts tracking a new checkpoint, unless one is still being persisted.
+    pub(crate) fn begin(&self, index: bool, hybrid_log: bool) -> Option<Arc<Progress>> {
+        let mut current = self.current.lock().unwrap();
+        if let Some(progress) = current.as_ref() {
+            if !progress.state.lock().unwrap().complete {
+                return None;
+            }
+        }
+        let sessions = match hybrid_log {
+            true => self.sessions.lock().unwrap().clone(),
+            false => HashSet::new(),
+        };
+        let progress = Arc::new(Progress::new(index, sessions));
+        *current = Some(Arc::clone(&progress));
+        Some(progress)
+    }
+
+    /// Sets the token of a started checkpoint, or forgets it if FASTER did not start it.
+    pub(crate) fn started(&self, progress: &Arc<Progress>, token: Option<CheckpointToken>) {
+        if token.is_some() {
+            progress.state.lock().unwrap().token = token;
+        } else {
+            let mut current = self.current.lock().unwrap();
+            if current.as_ref().is_some_and(|p| Arc::ptr_eq(p, progress)) {
+                *current = None;
+            }
+        }
+    }
+
+    fn current(&self) -> Option<Arc<Progress>> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// The token of the checkpoint that is being persisted, if any.
+    pub(crate) fn running(&self) -> Option<CheckpointToken> {
+        let progress = self.current()?;
+        let state = progress.state.lock().unwrap();
+        match state.complete {
+            true => None,
+            false => state.token,
+        }
+    }
+}
+
+/// Blocks the calling thread until the checkpoint of `progress` has been persisted, running
+/// `drive` in between to let the session of this thread make progress.
+fn wait_for<'e, F>(progress: &Progress, mut drive: F) -> Result<PersistedCheckpoint, FasterError<'e>>
+where
+    F: FnMut() -> Result<(), FasterError<'e>>,
+{
+    loop {
+        if let Some(result) = progress.result() {
+            return result;
+        }
+        drive()?;
+        let state = progress.state.lock().unwrap();
+        if !state.complete {
+            // Other sessions may persist their part in the meantime
+            let _ = progress
+                .persisted
+                .wait_timeout(state, std::time::Duration::from_millis(1))
+                .unwrap();
+        }
+    }
+}
+
+pub(crate) unsafe extern "C" fn checkpoint_callback(
+    tracker: *mut libc::c_void,
+    kind: ffi::faster_checkpoint_kind,
+    status: ffi::faster_status,
+    persistent_serial: u64,
+) {
+    let tracker = &*(tracker as *const CheckpointTracker);
+    let progress = match tracker.current() {
+        Some(progress) => progress,
+        None => return,
+    };
+    let status = Status::from(status);
+    // The thread may run sessions of other stores too, which are not registered with this one
+    let session_id = active_session_ids().into_iter().find(|id| tracker.has_session(*id));
+    progress.update(|state| {
+        if status.is_error() {
+            state.status = status;
+        }
+        if kind == ffi::faster_checkpoint_kind_IndexCheckpoint {
+            state.index_pending = false;
+        } else if let Some(session_id) = session_id {
+            if state.sessions_pending.remove(&session_id) {
+                state.sessions.push(SessionCheckpoint {
+                    session_id,
+                    persistent_serial,
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{latest_recoverable, CheckpointInfo, CheckpointKind, RetentionPolicy};
+    use crate::guid::CheckpointToken;
+    use std::time::{Duration, SystemTime};
+
+    fn token(id: u8) -> CheckpointToken {
+        CheckpointToken::from_bytes([id; 16])
+    }
+
+    fn info(id: u8, kind: CheckpointKind, version: u32, address: u64) -> CheckpointInfo {
+        CheckpointInfo {
+            token: token(id),
+            kind,
+            version,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(version as u64),
+            address,
+        }
+    }
+
+    #[test]
+    fn pairs_full_checkpoints_by_token() {
+        let checkpoints = vec![
+            info(1, CheckpointKind::Index, 1, 100),
+            info(1, CheckpointKind::HybridLog, 1, 200),
+            info(2, CheckpointKind::Index, 2, 300),
+            info(2, CheckpointKind::HybridLog, 2, 400),
+        ];
+        let latest = latest_recoverable(&checkpoints);
+        assert_eq!(latest, Some((token(2), token(2))));
+    }
+
+    #[test]
+    fn pairs_log_checkpoint_with_earlier_index() {
+        let checkpoints = vec![
+            info(1, CheckpointKind::Index, 1, 100),
+            info(2, CheckpointKind::HybridLog, 2, 200),
+            info(3, CheckpointKind::Index, 3, 300),
+        ];
+        let latest = latest_recoverable(&checkpoints);
+        assert_eq!(latest, Some((token(1), token(2))));
+    }
+
+    #[test]
+    fn requires_an_index_checkpoint() {
+        let checkpoints = vec![info(2, CheckpointKind::HybridLog, 1, 200)];
+        assert_eq!(latest_recoverable(&checkpoints), None);
+    }
+
+    fn ids(checkpoints: Vec<&CheckpointInfo>) -> Vec<(u8, CheckpointKind)> {
+        checkpoints.iter().map(|c| (c.token.as_bytes()[0], c.kind)).collect()
+    }
+
+    #[test]
+    fn keep_all_expires_nothing() {
+        let checkpoints = vec![
+            info(1, CheckpointKind::Index, 1, 100),
+            info(1, CheckpointKind::HybridLog, 1, 200),
+            info(2, CheckpointKind::HybridLog, 2, 300),
+        ];
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        assert!(RetentionPolicy::keep_all().expired(&checkpoints, now).is_empty());
+    }
+
+    #[test]
+    fn keep_last_keeps_index_of_kept_log() {
+        let checkpoints = vec![
+            info(1, CheckpointKind::Index, 1, 100),
+            info(1, CheckpointKind::HybridLog, 1, 200),
+            info(2, CheckpointKind::Index, 2, 300),
+            info(2, CheckpointKind::HybridLog, 2, 400),
+            info(3, CheckpointKind::HybridLog, 3, 500),
+            info(4, CheckpointKind::HybridLog, 4, 600),
+        ];
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let expired = RetentionPolicy::keep_last(2).expired(&checkpoints, now);
+        assert_eq!(
+            ids(expired),
+            vec![(1, CheckpointKind::HybridLog), (2, CheckpointKind::HybridLog)]
+        );
+    }
+
+    #[test]
+    fn keep_newer_than_never_expires_latest_recoverable() {
+        let checkpoints = vec![
+            info(1, CheckpointKind::Index, 1, 100),
+            info(1, CheckpointKind::HybridLog, 1, 200),
+            info(2, CheckpointKind::Index, 2, 300),
+            info(2, CheckpointKind::HybridLog, 2, 400),
+            info(3, CheckpointKind::Index, 3, 500),
+        ];
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let policy = RetentionPolicy::keep_newer_than(Duration::from_secs(10));
+        let expired = policy.expired(&checkpoints, now);
+        assert_eq!(
+            ids(expired),
+            vec![
+                (1, CheckpointKind::Index),
+                (1, CheckpointKind::HybridLog),
+                (3, CheckpointKind::Index),
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+extern crate bincode;
+
+use crate::FasterError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encodes keys and values of type `T` into the bytes stored by FASTER.
+///
+/// A `FasterKv` is generic over its codec, so the same store layout can be shared with
+/// services that do not use serde, or skip serialization entirely for raw bytes.
+pub trait Codec<T> {
+    /// Encodes `value`, either by writing into `buf` or by borrowing the bytes of `value`
+    /// directly.
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>>;
+
+    /// Decodes a value from the bytes of a record.
+    fn decode(bytes: &[u8]) -> Result<T, FasterError<'static>>;
+
+    /// Whether fixed-width numbers are encoded as their little-endian bytes and nothing else, so
+    /// that RMW can update them in place with `FasterRmw::rmw_bytes` instead of decoding them.
+    const LITTLE_ENDIAN_NUMBERS: bool = false;
+}
+
+/// Encodes values of type `T` without being able to decode them, e.g. borrowed byte slices.
+/// Operations that only encode their keys or values, like `Session::upsert`, take any `Encode`
+/// type, and every `Codec` is an `Encode`.
+pub trait Encode<T: ?Sized> {
+    /// See `Codec::encode`.
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>>;
+}
+
+impl<C, T> Encode<T> for C
+where
+    C: Codec<T>,
+{
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>> {
+        <C as Codec<T>>::encode(value, buf)
+    }
+}
+
+/// Encodes serde types with `bincode`. This is the default codec of a `FasterKv`.
+pub struct Bincode;
+
+impl<T> Codec<T> for Bincode
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>> {
+        buf.clear();
+        bincode::serialize_into(&mut *buf, value)?;
+        Ok(buf.as_slice())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, FasterError<'static>> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    // The default bincode configuration uses fixed-width little-endian integers
+    const LITTLE_ENDIAN_NUMBERS: bool = true;
+}
+
+/// Stores byte blobs as they are, without any serialization. The bytes are handed to FASTER
+/// without being copied.
+pub struct RawBytes;
+
+impl Codec<Vec<u8>> for RawBytes {
+    fn encode<'a>(
+        value: &'a Vec<u8>,
+        _buf: &'a mut Vec<u8>,
+    ) -> Result<&'a [u8], FasterError<'static>> {
+        Ok(value.as_slice())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Vec<u8>, FasterError<'static>> {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Keys and values can also be written from byte slices, which are handed to FASTER without
+/// being copied into a `Vec` first.
+impl Encode<[u8]> for RawBytes {
+    fn encode<'a>(value: &'a [u8], _buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>> {
+        Ok(value)
+    }
+}
+
+/// Encodes serde types with `postcard`.
+#[cfg(feature = "postcard")]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl<T> Codec<T> for Postcard
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>> {
+        buf.clear();
+        *buf = postcard::to_extend(value, std::mem::take(buf))
+            .map_err(|err| FasterError::Serialization(Box::new(err)))?;
+        Ok(buf.as_slice())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, FasterError<'static>> {
+        postcard::from_bytes(bytes).map_err(|err| FasterError::Serialization(Box::new(err)))
+    }
+}
+
+/// Encodes serde types as MessagePack, with structs written as maps so that other languages
+/// can read them by field name.
+#[cfg(feature = "msgpack")]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl<T> Codec<T> for MessagePack
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn encode<'a>(value: &'a T, buf: &'a mut Vec<u8>) -> Result<&'a [u8], FasterError<'static>> {
+        buf.clear();
+        rmp_serde::encode::write_named(&mut *buf, value)
+            .map_err(|err| FasterError::Serialization(Box::new(err)))?;
+        Ok(buf.as_slice())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, FasterError<'static>> {
+        rmp_serde::from_slice(bytes).map_err(|err| FasterError::Serialization(Box::new(err)))
+    }
+}
@@ -1,3 +1,5 @@
+use crate::guid::ParseGuidError;
+use crate::status::Status;
 use std::error::Error;
 use std::{fmt, io};
 
@@ -7,7 +9,11 @@ pub enum FasterError<'a> {
     InvalidType,
     RecoveryError,
     CheckpointError,
+    CheckpointInUse,
+    InvalidToken,
     BuilderError(&'a str),
+    OperationError(Status),
+    Serialization(Box<dyn Error + Send + Sync>),
 }
 
 impl<'a> fmt::Display for FasterError<'a> {
@@ -17,7 +23,11 @@ impl<'a> fmt::Display for FasterError<'a> {
             FasterError::InvalidType => write!(f, "Cannot call method with in-memory FasterKv"),
             FasterError::RecoveryError => write!(f, "Failed to recover"),
             FasterError::CheckpointError => write!(f, "Checkpoint failed"),
+            FasterError::CheckpointInUse => write!(f, "Checkpoint is needed for recovery or still being persisted"),
+            FasterError::InvalidToken => write!(f, "Invalid session id or checkpoint token"),
             FasterError::BuilderError(err) => write!(f, "Builder error: {}", err),
+            FasterError::OperationError(status) => write!(f, "Operation failed: {}", status),
+            FasterError::Serialization(err) => write!(f, "Serialization failed: {}", err),
         }
     }
 }
@@ -28,4 +38,16 @@ impl<'a> From<io::Error> for FasterError<'a> {
     }
 }
 
+impl<'a> From<ParseGuidError> for FasterError<'a> {
+    fn from(_: ParseGuidError) -> Self {
+        FasterError::InvalidToken
+    }
+}
+
+impl<'a> From<bincode::Error> for FasterError<'a> {
+    fn from(e: bincode::Error) -> Self {
+        FasterError::Serialization(Box::new(e))
+    }
+}
+
 impl<'a> Error for FasterError<'a> {}
@@ -1,66 +1,430 @@
-extern crate bincode;
 extern crate libc;
 extern crate libfaster_sys as ffi;
 
-use crate::status;
+use crate::codec::Codec;
+use crate::status::Status;
+use crate::FasterError;
 
-use bincode::deserialize;
-use local_channel::mpsc::Sender;
+use local_channel::mpsc::{channel, Receiver, Sender};
 use log::error;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::cell::RefCell;
 
 pub trait FasterKey: DeserializeOwned + Serialize {}
 
 pub trait FasterValue: DeserializeOwned + Serialize {}
 
+thread_local! {
+  // Errors raised inside callbacks cannot unwind through FASTER, so they are parked here
+  // until the operation (or `complete_pending`) that triggered the callback returns. Pending
+  // operations that report their result to a receiver send their errors there instead, so
+  // that they are not returned by an unrelated call.
+  static CALLBACK_ERROR: RefCell<Option<FasterError<'static>>> = const { RefCell::new(None) };
+}
+
+fn set_callback_error(err: FasterError<'static>) {
+  CALLBACK_ERROR.with(|slot| *slot.borrow_mut() = Some(err));
+}
+
+/// The outcome of an upsert, RMW or delete that went pending, sent once it has completed.
+pub(crate) type Completed = Result<Status, FasterError<'static>>;
+
+/// Takes the last error raised by a callback on the current thread, if any.
+pub(crate) fn take_callback_error() -> Option<FasterError<'static>> {
+  CALLBACK_ERROR.with(|slot| slot.borrow_mut().take())
+}
+
 #[inline(always)]
-pub unsafe extern "C" fn read_callback<T>(sender: *mut libc::c_void, value: *const u8, length: u64, status: u32)
+pub unsafe extern "C" fn read_callback<C, T>(
+  sender: *mut libc::c_void,
+  value: *const u8,
+  length: u64,
+  status: ffi::faster_status,
+)
 where
-  T: DeserializeOwned,
+  C: Codec<T>,
 {
-  let boxed_sender = Box::from_raw(sender as *mut Sender<T>);
+  let boxed_sender = Box::from_raw(sender as *mut Sender<Result<T, FasterError<'static>>>);
   let sender = *boxed_sender;
-  if status == status::OK.into() {
-    let val = deserialize(std::slice::from_raw_parts(value, length as usize)).unwrap();
-    if let Err(_) = sender.send(val) {
+  // Errors go to the receiver of the read that raised them. Dropping the sender of a key that
+  // does not exist closes the receiver without a value.
+  if let Some(result) = read_result::<C, T>(value, length, status).transpose() {
+    if sender.send(result).is_err() {
+      error!("Error sending faster_read response.");
+    }
+  }
+}
+
+/// The result of a read of a `multi_get`, which stays `None` until the read has completed.
+pub(crate) type MultiGetSlot<T> = Option<Result<Option<T>, FasterError<'static>>>;
+
+/// Stores the result of a read of a `multi_get` in its slot, which outlives pending reads.
+#[inline(always)]
+pub unsafe extern "C" fn multi_get_callback<C, T>(
+  slot: *mut libc::c_void,
+  value: *const u8,
+  length: u64,
+  status: ffi::faster_status,
+) where
+  C: Codec<T>,
+{
+  let slot = &mut *(slot as *mut MultiGetSlot<T>);
+  *slot = Some(read_result::<C, T>(value, length, status));
+}
+
+/// Sends the result of a read to the `Sender<Result<Option<T>, _>>` boxed in `sender`, which
+/// yields `None` if the key does not exist. Unlike `read_callback`, every read sends a result.
+#[inline(always)]
+pub unsafe extern "C" fn read_option_callback<C, T>(
+  sender: *mut libc::c_void,
+  value: *const u8,
+  length: u64,
+  status: ffi::faster_status,
+) where
+  C: Codec<T>,
+{
+  let sender = Box::from_raw(sender as *mut Sender<Result<Option<T>, FasterError<'static>>>);
+  if sender.send(read_result::<C, T>(value, length, status)).is_err() {
+    error!("Error sending faster_read response.");
+  }
+}
+
+unsafe fn read_result<C, T>(value: *const u8, length: u64, status: ffi::faster_status) -> Result<Option<T>, FasterError<'static>>
+where
+  C: Codec<T>,
+{
+  match Status::from(status) {
+    Status::Ok => C::decode(std::slice::from_raw_parts(value, length as usize)).map(Some),
+    Status::NotFound => Ok(None),
+    status => Err(FasterError::OperationError(status)),
+  }
+}
+
+/// State of a `read_with` call. It lives on the caller's stack unless the read goes pending, in
+/// which case `read_with_relocate` moves the reader to the heap.
+pub(crate) struct ReadWithState<F, R> {
+  reader: Option<F>,
+  pub(crate) result: Option<R>,
+  pub(crate) receiver: Option<Receiver<Result<R, FasterError<'static>>>>,
+  sender: Option<Sender<Result<R, FasterError<'static>>>>,
+}
+
+impl<F, R> ReadWithState<F, R> {
+  pub(crate) fn new(reader: F) -> Self {
+    ReadWithState {
+      reader: Some(reader),
+      result: None,
+      receiver: None,
+      sender: None,
+    }
+  }
+}
+
+#[inline(always)]
+pub unsafe extern "C" fn read_with_callback<F, R>(
+  target: *mut libc::c_void,
+  value: *const u8,
+  length: u64,
+  status: ffi::faster_status,
+) where
+  F: FnOnce(&[u8]) -> R,
+{
+  let target = target as *mut ReadWithState<F, R>;
+  let found = Status::from(status) == Status::Ok;
+  if (*target).sender.is_none() {
+    // Completed synchronously, the state is still owned by the caller
+    if let Err(err) = Status::from(status).into_result() {
+      set_callback_error(err);
+    } else if found {
+      let reader = (*target).reader.take();
+      (*target).result = reader.map(|reader| reader(std::slice::from_raw_parts(value, length as usize)));
+    }
+    return;
+  }
+  let state = Box::from_raw(target);
+  let result = match (Status::from(status).into_result(), state.reader) {
+    (Err(err), _) => Err(err),
+    (Ok(_), Some(reader)) if found => Ok(reader(std::slice::from_raw_parts(value, length as usize))),
+    // Dropping the sender closes the receiver without a value
+    _ => return,
+  };
+  if let Some(sender) = state.sender {
+    if sender.send(result).is_err() {
       error!("Error sending faster_read response.");
     }
   }
 }
 
 #[inline(always)]
-pub unsafe extern "C" fn rmw_callback<T>(
+pub unsafe extern "C" fn read_with_relocate<F, R>(target: *mut libc::c_void) -> *mut libc::c_void {
+  let state = &mut *(target as *mut ReadWithState<F, R>);
+  if state.sender.is_some() {
+    // Already on the heap
+    return target;
+  }
+  let (sender, receiver) = channel();
+  state.receiver = Some(receiver);
+  let relocated = ReadWithState {
+    reader: state.reader.take(),
+    result: None,
+    receiver: None,
+    sender: Some(sender),
+  };
+  Box::into_raw(Box::new(relocated)) as *mut libc::c_void
+}
+
+/// Reports the outcome of a conditional write, with the state of a `read_with` call. The reader
+/// gets the value the record had before the write, and whether the new value was written.
+#[inline(always)]
+pub unsafe extern "C" fn conditional_callback<F, R>(
+  target: *mut libc::c_void,
+  current: *const u8,
+  length: u64,
+  swapped: bool,
+  status: ffi::faster_status,
+) where
+  F: FnOnce(Option<&[u8]>, bool) -> Result<R, FasterError<'static>>,
+{
+  let target = target as *mut ReadWithState<F, R>;
+  let completed = Status::from(status) == Status::Ok;
+  let current = match current.is_null() {
+    true => None,
+    false => Some(std::slice::from_raw_parts(current, length as usize)),
+  };
+  if (*target).sender.is_none() {
+    // Completed synchronously, the state is still owned by the caller
+    if let Err(err) = Status::from(status).into_result() {
+      set_callback_error(err);
+    } else if let (true, Some(reader)) = (completed, (*target).reader.take()) {
+      match reader(current, swapped) {
+        Ok(result) => (*target).result = Some(result),
+        Err(err) => set_callback_error(err),
+      }
+    }
+    return;
+  }
+  let state = Box::from_raw(target);
+  // Errors go to the receiver of the write that raised them
+  let result = match (Status::from(status).into_result(), state.reader) {
+    (Err(err), _) => Err(err),
+    (Ok(_), Some(reader)) if completed => reader(current, swapped),
+    // Dropping the sender closes the receiver without a value
+    _ => return,
+  };
+  if let Some(sender) = state.sender {
+    if sender.send(result).is_err() {
+      error!("Error sending conditional write response.");
+    }
+  }
+}
+
+/// State of a `rmw_with` call, with the closures that compute the encoded new value of a record.
+/// Like a `ReadWithState`, it lives on the caller's stack unless the RMW goes pending.
+pub(crate) struct RmwWithState<I, U> {
+  initial: Option<I>,
+  update: Option<U>,
+  // FASTER may size a new record more than once, but the initial closure only runs once
+  initial_value: Option<Vec<u8>>,
+  value: Vec<u8>,
+  relocated: bool,
+  // Notified once a RMW that went pending has completed
+  done: Option<Sender<Completed>>,
+}
+
+impl<I, U> RmwWithState<I, U>
+where
+  I: FnOnce() -> Result<Vec<u8>, FasterError<'static>>,
+  U: Fn(&[u8]) -> Result<Vec<u8>, FasterError<'static>>,
+{
+  pub(crate) fn new(initial: I, update: U, done: Option<Sender<Completed>>) -> Self {
+    RmwWithState {
+      initial: Some(initial),
+      update: Some(update),
+      initial_value: None,
+      value: Vec::new(),
+      relocated: false,
+      done,
+    }
+  }
+
+  fn compute(&mut self, current: Option<&[u8]>) -> Result<Vec<u8>, FasterError<'static>> {
+    match (current, &self.update) {
+      (Some(current), Some(update)) => update(current),
+      (Some(current), None) => Ok(current.to_vec()),
+      (None, _) => {
+        if let Some(initial) = self.initial.take() {
+          self.initial_value = Some(initial()?);
+        }
+        Ok(self.initial_value.clone().unwrap_or_default())
+      }
+    }
+  }
+}
+
+// FASTER_RMW_NOT_IN_PLACE and FASTER_RMW_FAILED, which bindgen cannot evaluate
+const RMW_NOT_IN_PLACE: u64 = u64::MAX;
+const RMW_FAILED: u64 = u64::MAX;
+
+#[inline(always)]
+pub unsafe extern "C" fn rmw_with_callback<I, U>(
+  target: *mut libc::c_void,
+  current: *const u8,
+  length: u64,
+  value: *mut *const u8,
+) -> u64
+where
+  I: FnOnce() -> Result<Vec<u8>, FasterError<'static>>,
+  U: Fn(&[u8]) -> Result<Vec<u8>, FasterError<'static>>,
+{
+  let state = &mut *(target as *mut RmwWithState<I, U>);
+  let current = match current.is_null() {
+    true => None,
+    false => Some(std::slice::from_raw_parts(current, length as usize)),
+  };
+  state.value = match (state.compute(current), current) {
+    (Ok(computed), _) => computed,
+    // The RMW cannot be aborted once it updates a record, so the current value is kept as is
+    (Err(err), Some(current)) => {
+      set_callback_error(err);
+      current.to_vec()
+    }
+    // FASTER only creates the record once its value has been computed, so none is created
+    (Err(err), None) => {
+      set_callback_error(err);
+      return RMW_FAILED;
+    }
+  };
+  *value = state.value.as_ptr();
+  state.value.len() as u64
+}
+
+#[inline(always)]
+pub unsafe extern "C" fn rmw_with_relocate<I, U>(target: *mut libc::c_void) -> *mut libc::c_void {
+  let state = &mut *(target as *mut RmwWithState<I, U>);
+  if state.relocated {
+    // Already on the heap
+    return target;
+  }
+  let relocated = RmwWithState {
+    initial: state.initial.take(),
+    update: state.update.take(),
+    initial_value: state.initial_value.take(),
+    value: Vec::new(),
+    relocated: true,
+    done: state.done.take(),
+  };
+  Box::into_raw(Box::new(relocated)) as *mut libc::c_void
+}
+
+#[inline(always)]
+pub unsafe extern "C" fn rmw_with_done<I, U>(target: *mut libc::c_void, status: ffi::faster_status) {
+  let state = &mut *(target as *mut RmwWithState<I, U>);
+  match state.done.take() {
+    // The receiver is gone if the caller stopped waiting
+    Some(done) => {
+      let _ = done.send(completed(status));
+    }
+    None => {
+      if let Err(err) = Status::from(status).into_result() {
+        set_callback_error(err);
+      }
+    }
+  }
+  if state.relocated {
+    drop(Box::from_raw(target as *mut RmwWithState<I, U>));
+  }
+}
+
+/// Sends the outcome of an upsert, RMW or delete that went pending to the `Sender<Completed>`
+/// boxed in `target`.
+#[inline(always)]
+pub(crate) unsafe extern "C" fn completion_callback(target: *mut libc::c_void, status: ffi::faster_status) {
+  let done = Box::from_raw(target as *mut Sender<Completed>);
+  // The receiver is gone if the caller stopped waiting
+  let _ = done.send(completed(status));
+}
+
+/// The outcome of an operation that FASTER has just completed. FASTER completes an operation right
+/// after running its update callbacks, so an error that they raised belongs to this operation.
+fn completed(status: ffi::faster_status) -> Completed {
+  match take_callback_error() {
+    Some(err) => Err(err),
+    None => Status::from(status).into_result(),
+  }
+}
+
+
+#[inline(always)]
+pub unsafe extern "C" fn rmw_callback<C, T>(
   current: *const u8,
   length_current: u64,
-  modification: *mut u8,
+  modification: *const u8,
   length_modification: u64,
   dst: *mut u8,
+  in_place: bool,
 ) -> u64
 where
-  T: Serialize + DeserializeOwned + FasterRmw,
+  C: Codec<T>,
+  T: FasterRmw,
 {
-  let val: T = deserialize(std::slice::from_raw_parts(current, length_current as usize)).unwrap();
-  let modif = deserialize(std::slice::from_raw_parts_mut(
-    modification,
-    length_modification as usize,
-  ))
-  .unwrap();
-  let modified = val.rmw(modif);
-  let encoded = bincode::serialize(&modified).unwrap();
-  let size = encoded.len();
-  if dst != std::ptr::null_mut() {
-    encoded.as_ptr().copy_to(dst, size);
+  let modification = std::slice::from_raw_parts(modification, length_modification as usize);
+  if in_place && C::LITTLE_ENDIAN_NUMBERS && !current.is_null() {
+    // `dst` aliases `current`, so the record is updated without decoding it
+    if T::rmw_bytes(std::slice::from_raw_parts_mut(dst, length_current as usize), modification) {
+      return length_current;
+    }
+  }
+  let current = match current.is_null() {
+    true => None,
+    false => Some(std::slice::from_raw_parts(current, length_current as usize)),
+  };
+  let mut buf = Vec::new();
+  let modified = C::decode(modification).and_then(|modification| match current {
+    Some(current) => Ok(modify(C::decode(current)?, modification)),
+    None => Ok(T::initial(modification)),
+  });
+  let encoded = match modified {
+    Ok(ref modified) => C::encode(modified, &mut buf),
+    Err(err) => Err(err),
+  };
+  let result = match (encoded, current) {
+    (Ok(encoded), _) => encoded,
+    // The RMW cannot be aborted once it updates a record, so the current value is kept as is
+    (Err(err), Some(current)) => {
+      set_callback_error(err);
+      current
+    }
+    // FASTER only creates the record once its value has been computed, so none is created
+    (Err(err), None) => {
+      set_callback_error(err);
+      return RMW_FAILED;
+    }
+  };
+  let size = result.len();
+  if in_place && !matches!(current, Some(current) if size <= current.len()) {
+    return RMW_NOT_IN_PLACE;
+  }
+  if !dst.is_null() {
+    // `dst` aliases `current` for in-place updates
+    result.as_ptr().copy_to(dst, size);
   }
   size as u64
 }
 
+fn modify<T: FasterRmw>(mut current: T, modification: T) -> T {
+  match current.rmw_in_place(&modification) {
+    true => current,
+    false => current.rmw(modification),
+  }
+}
+
 pub trait FasterRmw: DeserializeOwned + Serialize {
   /// Specify custom Read-Modify-Write logic
   ///
   /// # Example
   /// ```
-  /// use faster_rs::{status, FasterKv, FasterRmw};
+  /// use faster_rs::{FasterKv, FasterRmw, Status};
   /// use serde_derive::{Deserialize, Serialize};
   /// use local_channel::mpsc::Receiver;
   /// use monoio::IoUringDriver;
@@ -79,14 +443,72 @@ pub trait FasterRmw: DeserializeOwned + Serialize {
   ///
   /// monoio::start::<IoUringDriver, _>(async move {
   ///   let store = FasterKv::default();
+  ///   let session = store.session();
   ///   let key = 5u64;
   ///   let value = MyU64 { value: 12 };
   ///   let modification = MyU64 { value: 17 };
-  ///   store.upsert(&key, &value, 1);
-  ///   store.rmw(&key, &modification, 1);
-  ///   let (status, mut recv): (u8, Receiver<MyU64>) = store.read(&key, 1);
-  ///   assert_eq!(status, status::OK);
-  ///   assert_eq!(recv.recv().await.unwrap().value, value.value + modification.value);
+  ///   session.upsert(&key, &value).unwrap();
+  ///   session.rmw(&key, &modification).unwrap();
+  ///   let (status, mut recv): (Status, Receiver<Result<MyU64, FasterError>>) = session.read(&key).unwrap();
+  ///   assert_eq!(status, Status::Ok);
+  ///   assert_eq!(recv.recv().await.unwrap().unwrap().value, value.value + modification.value);
   /// });
   fn rmw(&self, modification: Self) -> Self;
+
+  /// The value of a key that has no record yet, computed from the first modification. By
+  /// default, the modification itself becomes the value.
+  /// If it cannot be encoded, the RMW fails and the key is left without a record.
+  ///
+  /// # Example
+  /// ```
+  /// use faster_rs::FasterRmw;
+  /// use serde_derive::{Deserialize, Serialize};
+  ///
+  /// // Counts the modifications along with their sum
+  /// #[derive(Serialize, Deserialize)]
+  /// struct Tally {
+  ///     count: u64,
+  ///     sum: u64,
+  /// }
+  /// impl FasterRmw for Tally {
+  ///     fn rmw(&self, modification: Self) -> Self {
+  ///         Tally {
+  ///             count: self.count + 1,
+  ///             sum: self.sum + modification.sum,
+  ///         }
+  ///     }
+  ///
+  ///     fn initial(modification: Self) -> Self {
+  ///         Tally {
+  ///             count: 1,
+  ///             sum: modification.sum,
+  ///         }
+  ///     }
+  /// }
+  /// ```
+  fn initial(modification: Self) -> Self {
+    modification
+  }
+
+  /// Applies `modification` to this value in place, instead of building a new value with `rmw`.
+  /// Returns false if it cannot, in which case `rmw` is used. It must have the same effect as
+  /// `rmw`.
+  ///
+  /// This avoids building a new value for every update of values like numbers. A value that does
+  /// not grow when encoded is also written over its record in place.
+  fn rmw_in_place(&mut self, _modification: &Self) -> bool {
+    false
+  }
+
+  /// Applies an encoded modification to the encoded value of a record in place, for values that
+  /// codecs like `Bincode` encode as fixed-width little-endian bytes. This skips decoding and
+  /// encoding the value altogether, and is implemented for the numeric types. Returns false if
+  /// the bytes do not have the width of such a value, in which case they are decoded. It must
+  /// have the same effect as `rmw`.
+  ///
+  /// It is only used for records in the mutable region of the log. Other records are copied
+  /// with `rmw`.
+  fn rmw_bytes(_current: &mut [u8], _modification: &[u8]) -> bool {
+    false
+  }
 }
@@ -0,0 +1,213 @@
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A GUID as FASTER uses it to identify sessions and checkpoints. Formatted as 36 hyphenated
+/// lowercase hex digits, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Guid([u8; 16]);
+
+// Where the hyphens of a formatted GUID are
+const HYPHENS: [usize; 4] = [8, 13, 18, 23];
+const FORMATTED_LEN: usize = 36;
+
+impl Guid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Guid {
+        Guid(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i == 4 || i == 6 || i == 8 || i == 10 {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    /// Parses the hyphenated format, in upper- or lowercase.
+    fn from_str(s: &str) -> Result<Guid, ParseGuidError> {
+        let bytes = s.as_bytes();
+        if bytes.len() != FORMATTED_LEN || HYPHENS.iter().any(|&i| bytes[i] != b'-') {
+            return Err(ParseGuidError);
+        }
+        let mut digits = bytes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !HYPHENS.contains(i))
+            .map(|(_, c)| (*c as char).to_digit(16).ok_or(ParseGuidError));
+        let mut guid = [0u8; 16];
+        for byte in guid.iter_mut() {
+            // Both digits are there, as the length has been checked
+            let high = digits.next().unwrap()?;
+            let low = digits.next().unwrap()?;
+            *byte = (high << 4 | low) as u8;
+        }
+        Ok(Guid(guid))
+    }
+}
+
+impl Serialize for Guid {
+    /// Serializes to the hyphenated format for human-readable formats, and to the 16 bytes of
+    /// the GUID otherwise.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => serializer.collect_str(self),
+            false => self.0.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Guid, D::Error> {
+        match deserializer.is_human_readable() {
+            true => String::deserialize(deserializer)?.parse().map_err(de::Error::custom),
+            false => <[u8; 16]>::deserialize(deserializer).map(Guid),
+        }
+    }
+}
+
+macro_rules! guid_newtype {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(Guid);
+
+        impl $name {
+            pub fn from_bytes(bytes: [u8; 16]) -> $name {
+                $name(Guid::from_bytes(bytes))
+            }
+
+            /// The 16-byte binary form.
+            pub fn as_bytes(&self) -> &[u8; 16] {
+                self.0.as_bytes()
+            }
+
+            pub fn guid(&self) -> Guid {
+                self.0
+            }
+        }
+
+        impl From<Guid> for $name {
+            fn from(guid: Guid) -> $name {
+                $name(guid)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseGuidError;
+
+            fn from_str(s: &str) -> Result<$name, ParseGuidError> {
+                s.parse().map($name)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                Guid::deserialize(deserializer).map($name)
+            }
+        }
+    };
+}
+
+guid_newtype!(
+    /// The id of a session, which is used to continue it after recovery.
+    SessionId
+);
+
+guid_newtype!(
+    /// The token of a checkpoint, which names its directories in the storage directory.
+    CheckpointToken
+);
+
+/// The error of parsing a string that is not a GUID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseGuidError;
+
+impl fmt::Display for ParseGuidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid GUID, expected 36 hyphenated hex digits")
+    }
+}
+
+impl Error for ParseGuidError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckpointToken, Guid, ParseGuidError, SessionId};
+
+    #[test]
+    fn parses_and_formats() {
+        let formatted = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let guid: Guid = formatted.parse().unwrap();
+        assert_eq!(guid.as_bytes()[0], 0x67);
+        assert_eq!(guid.as_bytes()[15], 0xc8);
+        assert_eq!(guid.to_string(), formatted);
+        assert_eq!(formatted.to_uppercase().parse::<Guid>(), Ok(guid));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in [
+            "",
+            "67e55044-10b1-426f-9247-bb680e5fe0c",
+            "67e55044-10b1-426f-9247-bb680e5fe0c8a",
+            "67e5504410b1-426f-9247-bb680e5fe0c8a",
+            "67e55044-10b1-426f-9247-bb680e5fe0cg",
+            "../../../../../../../../../etc/passw",
+        ]
+        .iter()
+        {
+            assert_eq!(malformed.parse::<Guid>(), Err(ParseGuidError), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn newtypes_serialize_as_text_or_bytes() {
+        let formatted = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let token: CheckpointToken = formatted.parse().unwrap();
+        let bytes = bincode::serialize(&token).unwrap();
+        assert_eq!(bytes, token.as_bytes().to_vec());
+        assert_eq!(bincode::deserialize::<CheckpointToken>(&bytes).unwrap(), token);
+
+        let id = SessionId::from(token.guid());
+        assert_eq!(format!("{:?}", id), format!("SessionId({})", formatted));
+    }
+}
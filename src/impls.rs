@@ -1,7 +1,8 @@
 use crate::{FasterKey, FasterRmw, FasterValue};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::ops::Add;
+use std::convert::TryFrom;
+use std::mem;
 
 use std::collections::HashSet;
 use std::hash::Hash;
@@ -10,18 +11,56 @@ impl<T> FasterKey for T where T: Serialize + DeserializeOwned {}
 impl<T> FasterValue for T where T: Serialize + DeserializeOwned {}
 
 macro_rules! primitive_impl {
-    ($ty:ident, $method:ident $($cast:tt)*) => {
+    ($ty:ident, $method:ident) => {
         impl FasterRmw for $ty {
             #[inline]
             fn rmw(&self, modification: Self) -> Self {
                 $method(*self, modification)
             }
+
+            #[inline]
+            fn rmw_in_place(&mut self, modification: &Self) -> bool {
+                *self = $method(*self, *modification);
+                true
+            }
+        }
+    };
+}
+
+// Numbers are also updated on their little-endian bytes
+macro_rules! numeric_impl {
+    ($ty:ident, $method:ident) => {
+        impl FasterRmw for $ty {
+            #[inline]
+            fn rmw(&self, modification: Self) -> Self {
+                $method(*self, modification)
+            }
+
+            #[inline]
+            fn rmw_in_place(&mut self, modification: &Self) -> bool {
+                *self = $method(*self, *modification);
+                true
+            }
+
+            #[inline]
+            fn rmw_bytes(current: &mut [u8], modification: &[u8]) -> bool {
+                type Bytes = [u8; mem::size_of::<$ty>()];
+                match (Bytes::try_from(&*current), Bytes::try_from(modification)) {
+                    (Ok(value), Ok(modification)) => {
+                        let value = $method($ty::from_le_bytes(value), $ty::from_le_bytes(modification));
+                        current.copy_from_slice(&value.to_le_bytes());
+                        true
+                    }
+                    // e.g. a usize that bincode encoded as 8 bytes on a 32-bit target
+                    _ => false,
+                }
+            }
         }
     };
 }
 
 macro_rules! owned_impl {
-    ($ty:ident, $method:ident $($cast:tt)*) => {
+    ($ty:ident, $method:ident) => {
         impl FasterRmw for $ty {
             #[inline]
             fn rmw(&self, modification: Self) -> Self {
@@ -36,23 +75,56 @@ fn rmw_bool(_old: bool, new: bool) -> bool {
 }
 primitive_impl!(bool, rmw_bool);
 
-fn rmw_add<T: Add<Output = T>>(current: T, modification: T) -> T {
-    current + modification
+// Integers wrap around instead of panicking on overflow, as the sum is computed in a callback
+// from FASTER, which a panic cannot unwind through
+trait WrappingAdd {
+    fn wrapping_add(self, other: Self) -> Self;
+}
+
+macro_rules! wrapping_add_impl {
+    ($($ty:ident)*) => {
+        $(impl WrappingAdd for $ty {
+            #[inline]
+            fn wrapping_add(self, other: Self) -> Self {
+                $ty::wrapping_add(self, other)
+            }
+        })*
+    };
+}
+wrapping_add_impl!(isize i8 i16 i32 i64 i128 usize u8 u16 u32 u64 u128);
+
+// Floats follow IEEE 754 instead: a sum that overflows becomes an infinity, and NaN propagates
+impl WrappingAdd for f32 {
+    #[inline]
+    fn wrapping_add(self, other: Self) -> Self {
+        self + other
+    }
+}
+
+impl WrappingAdd for f64 {
+    #[inline]
+    fn wrapping_add(self, other: Self) -> Self {
+        self + other
+    }
+}
+
+fn rmw_add<T: WrappingAdd>(current: T, modification: T) -> T {
+    current.wrapping_add(modification)
 }
-primitive_impl!(isize, rmw_add);
-primitive_impl!(i8, rmw_add);
-primitive_impl!(i16, rmw_add);
-primitive_impl!(i32, rmw_add);
-primitive_impl!(i64, rmw_add);
-primitive_impl!(i128, rmw_add);
-primitive_impl!(usize, rmw_add);
-primitive_impl!(u8, rmw_add);
-primitive_impl!(u16, rmw_add);
-primitive_impl!(u32, rmw_add);
-primitive_impl!(u64, rmw_add);
-primitive_impl!(u128, rmw_add);
-primitive_impl!(f32, rmw_add);
-primitive_impl!(f64, rmw_add);
+numeric_impl!(isize, rmw_add);
+numeric_impl!(i8, rmw_add);
+numeric_impl!(i16, rmw_add);
+numeric_impl!(i32, rmw_add);
+numeric_impl!(i64, rmw_add);
+numeric_impl!(i128, rmw_add);
+numeric_impl!(usize, rmw_add);
+numeric_impl!(u8, rmw_add);
+numeric_impl!(u16, rmw_add);
+numeric_impl!(u32, rmw_add);
+numeric_impl!(u64, rmw_add);
+numeric_impl!(u128, rmw_add);
+numeric_impl!(f32, rmw_add);
+numeric_impl!(f64, rmw_add);
 
 fn rmw_char(_old: char, new: char) -> char {
     new
@@ -87,3 +159,44 @@ impl<T: Clone + Serialize + DeserializeOwned + Hash + Eq> FasterRmw for HashSet<
         union.cloned().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::FasterRmw;
+
+    #[test]
+    fn numbers_are_updated_on_their_bincode_bytes() {
+        let mut current = bincode::serialize(&40u64).unwrap();
+        let modification = bincode::serialize(&2u64).unwrap();
+        assert!(u64::rmw_bytes(&mut current, &modification));
+        assert_eq!(bincode::deserialize::<u64>(&current).unwrap(), 42);
+
+        let mut current = bincode::serialize(&1.5f32).unwrap();
+        let modification = bincode::serialize(&0.25f32).unwrap();
+        assert!(f32::rmw_bytes(&mut current, &modification));
+        assert_eq!(bincode::deserialize::<f32>(&current).unwrap(), 1.75);
+    }
+
+    #[test]
+    fn integers_wrap_around_on_overflow() {
+        assert_eq!(u64::MAX.rmw(2), 1);
+        assert_eq!(i8::MIN.rmw(-1), i8::MAX);
+
+        let mut current = u32::MAX;
+        assert!(current.rmw_in_place(&1));
+        assert_eq!(current, 0);
+
+        let mut current = bincode::serialize(&u64::MAX).unwrap();
+        let modification = bincode::serialize(&3u64).unwrap();
+        assert!(u64::rmw_bytes(&mut current, &modification));
+        assert_eq!(bincode::deserialize::<u64>(&current).unwrap(), 2);
+    }
+
+    #[test]
+    fn bytes_of_another_width_are_not_updated() {
+        let mut current = bincode::serialize(&40u32).unwrap();
+        let modification = bincode::serialize(&2u32).unwrap();
+        assert!(!u64::rmw_bytes(&mut current, &modification));
+        assert_eq!(current, bincode::serialize(&40u32).unwrap());
+    }
+}
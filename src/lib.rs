@@ -2,240 +2,99 @@ extern crate bincode;
 extern crate libc;
 extern crate libfaster_sys as ffi;
 
+mod backoff;
 mod builder;
+mod checkpoint;
+mod codec;
 mod faster_error;
 mod faster_traits;
+mod guid;
 mod impls;
+mod scan;
+mod scheduler;
+mod session;
 pub mod status;
 mod util;
 
-pub use crate::builder::FasterKvBuilder;
+pub use crate::builder::{Device, FasterKvBuilder, FasterKvConfig, DEFAULT_SEGMENT_SIZE};
+pub use crate::checkpoint::{
+    CheckPoint, CheckpointInfo, CheckpointKind, CheckpointMode, PersistedCheckpoint,
+    RetentionPolicy, SessionCheckpoint,
+};
+#[cfg(feature = "msgpack")]
+pub use crate::codec::MessagePack;
+#[cfg(feature = "postcard")]
+pub use crate::codec::Postcard;
+pub use crate::codec::{Bincode, Codec, Encode, RawBytes};
 pub use crate::faster_error::FasterError;
-use crate::faster_traits::{read_callback, rmw_callback};
 pub use crate::faster_traits::{FasterKey, FasterRmw, FasterValue};
-use crate::util::*;
+pub use crate::guid::{CheckpointToken, Guid, ParseGuidError, SessionId};
+pub use crate::scan::{Iter, LogRecord, RangeScan, RawIter};
+pub use crate::scheduler::CheckpointScheduler;
+pub use crate::session::{AsyncSession, Session};
+pub use crate::status::Status;
+use crate::checkpoint::CheckpointTracker;
+use crate::scheduler::SchedulerHandle;
+pub use crate::util::{Lookup, Recover, Swap};
 
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::fs;
-use local_channel::mpsc::{channel, Sender, Receiver};
+use std::io;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
-#[no_mangle]
-pub unsafe extern "C" fn deallocate_vec(vec: *mut u8, length: u64) {
-    drop(Vec::from_raw_parts(vec, length as usize, length as usize));
-}
-
-pub struct FasterKv {
+pub struct FasterKv<C = Bincode> {
     faster_t: *mut ffi::faster_t,
-    storage_dir: Option<String>,
+    config: FasterKvConfig,
+    // Shared with the checkpoint scheduler. Its address, which FASTER's checkpoint callback
+    // gets, stays stable
+    checkpoints: Arc<CheckpointTracker>,
+    scheduler: Option<SchedulerHandle>,
+    codec: PhantomData<C>,
 }
 
-impl FasterKv {
-    pub fn upsert<K, V>(&self, key: &K, value: &V, monotonic_serial_number: u64) -> u8
-    where
-        K: FasterKey,
-        V: FasterValue,
-    {
-        let mut encoded_key = bincode::serialize(key).unwrap();
-        let encoded_key_length = encoded_key.len();
-        let encoded_key_ptr = encoded_key.as_mut_ptr();
-        let mut encoded_value = bincode::serialize(value).unwrap();
-        let encoded_value_length = encoded_value.len();
-        let encoded_value_ptr = encoded_value.as_mut_ptr();
-        std::mem::forget(encoded_key);
-        std::mem::forget(encoded_value);
-        unsafe {
-            ffi::faster_upsert(
-                self.faster_t,
-                encoded_key_ptr,
-                encoded_key_length as u64,
-                encoded_value_ptr,
-                encoded_value_length as u64,
-                monotonic_serial_number,
-            )
-        }
-    }
-
-    pub fn read<K, V>(&self, key: &K, monotonic_serial_number: u64) -> (u8, Receiver<V>)
-    where
-        K: FasterKey,
-        V: FasterValue,
-    {
-        let mut encoded_key = bincode::serialize(key).unwrap();
-        let encoded_key_length = encoded_key.len();
-        let encoded_key_ptr = encoded_key.as_mut_ptr();
-        let (sender, receiver) = channel();
-        let sender_ptr: *mut Sender<V> = Box::into_raw(Box::new(sender));
-        std::mem::forget(encoded_key);
-        let status = unsafe {
-            ffi::faster_read(
-                self.faster_t,
-                encoded_key_ptr,
-                encoded_key_length as u64,
-                monotonic_serial_number,
-                Some(read_callback::<V>),
-                sender_ptr as *mut libc::c_void,
-            )
-        };
-        (status, receiver)
-    }
-
-    pub fn rmw<K, V>(&self, key: &K, value: &V, monotonic_serial_number: u64) -> u8
-    where
-        K: FasterKey,
-        V: FasterRmw,
-    {
-        let mut encoded_key = bincode::serialize(key).unwrap();
-        let encoded_key_length = encoded_key.len();
-        let encoded_key_ptr = encoded_key.as_mut_ptr();
-        let mut encoded_value = bincode::serialize(value).unwrap();
-        let encoded_value_length = encoded_value.len();
-        let encoded_value_ptr = encoded_value.as_mut_ptr();
-        std::mem::forget(encoded_key);
-        std::mem::forget(encoded_value);
-        unsafe {
-            ffi::faster_rmw(
-                self.faster_t,
-                encoded_key_ptr,
-                encoded_key_length as u64,
-                encoded_value_ptr,
-                encoded_value_length as u64,
-                monotonic_serial_number,
-                Some(rmw_callback::<V>),
-            )
-        }
-    }
-
-    /// Deletes a previously inserted key.
-    ///
-    /// Returns [NOT_FOUND](status/constant.NOT_FOUND.html) for un-inserted keys.
-    ///
-    /// # Example
-    /// ```
-    /// use monoio::{IoUringDriver};
-    /// use faster_rs::{FasterKv, status};
-    /// let store = FasterKv::default();
-    /// monoio::start::<IoUringDriver, _>(async move {
-    ///   let key = 1;
-    ///   let value = 42;
-    ///
-    ///   // Insert key-value
-    ///   store.upsert(&key, &value, 1);
-    ///
-    ///   // Read key-value
-    ///   let (res, mut recv) = store.read(&key, 1);
-    ///   assert_eq!(status::OK, res);
-    ///   assert_eq!(value, recv.recv().await.unwrap());
-    ///
-    ///   // Delete key-value
-    ///   store.delete(&key, 1);
-    ///
-    ///   // Re-read key-value and confirm deleted
-    ///   let (res, mut recv) = store.read::<i32, i32>(&key, 1);
-    ///   assert_eq!(status::NOT_FOUND, res);
-    ///   assert!(recv.recv().await.is_none());
-    /// });
-    /// ```
-    pub fn delete<K>(&self, key: &K, monotonic_serial_number: u64) -> u8
-    where
-        K: FasterKey,
-    {
-        let mut encoded_key = bincode::serialize(key).unwrap();
-        let encoded_key_length = encoded_key.len();
-        let encoded_key_ptr = encoded_key.as_mut_ptr();
-        std::mem::forget(encoded_key);
-        unsafe {
-            ffi::faster_delete(
-                self.faster_t,
-                encoded_key_ptr,
-                encoded_key_length as u64,
-                monotonic_serial_number,
-            )
-        }
-    }
-
+impl<C> FasterKv<C> {
     pub fn size(&self) -> u64 {
         unsafe { ffi::faster_size(self.faster_t) }
     }
 
-    pub fn checkpoint(&self) -> Result<CheckPoint, FasterError> {
-        if self.storage_dir.is_none() {
-            return Err(FasterError::InvalidType);
-        }
+    /// The configuration the store was built with.
+    pub fn config(&self) -> &FasterKvConfig {
+        &self.config
+    }
 
-        let result = unsafe { ffi::faster_checkpoint(self.faster_t) };
-        match result.is_null() {
-            true => Err(FasterError::CheckpointError),
-            false => {
-                let boxed = unsafe { Box::from_raw(result) }; // makes sure memory is dropped
-                let token_str =
-                    unsafe { CStr::from_ptr((*boxed).token).to_str().unwrap().to_owned() };
-
-                let checkpoint = CheckPoint {
-                    checked: (*boxed).checked,
-                    token: token_str,
-                };
-                Ok(checkpoint)
-            }
-        }
+    /// Starts a checkpoint of the index and hybrid log. The returned `CheckPoint` reports when
+    /// it has been persisted.
+    pub fn checkpoint(&self) -> Result<CheckPoint, FasterError> {
+        self.start_checkpoint(CheckpointMode::Full)
     }
 
     pub fn checkpoint_index(&self) -> Result<CheckPoint, FasterError> {
-        if self.storage_dir.is_none() {
-            return Err(FasterError::InvalidType);
-        }
-
-        let result = unsafe { ffi::faster_checkpoint_index(self.faster_t) };
-        match result.is_null() {
-            true => Err(FasterError::CheckpointError),
-            false => {
-                let boxed = unsafe { Box::from_raw(result) }; // makes sure memory is dropped
-                let token_str =
-                    unsafe { CStr::from_ptr((*boxed).token).to_str().unwrap().to_owned() };
-
-                let checkpoint = CheckPoint {
-                    checked: (*boxed).checked,
-                    token: token_str,
-                };
-                Ok(checkpoint)
-            }
-        }
+        self.start_checkpoint(CheckpointMode::Index)
     }
 
     pub fn checkpoint_hybrid_log(&self) -> Result<CheckPoint, FasterError> {
-        if self.storage_dir.is_none() {
-            return Err(FasterError::InvalidType);
-        }
+        self.start_checkpoint(CheckpointMode::HybridLog)
+    }
 
-        let result = unsafe { ffi::faster_checkpoint_hybrid_log(self.faster_t) };
-        match result.is_null() {
-            true => Err(FasterError::CheckpointError),
-            false => {
-                let boxed = unsafe { Box::from_raw(result) }; // makes sure memory is dropped
-                let token_str =
-                    unsafe { CStr::from_ptr((*boxed).token).to_str().unwrap().to_owned() };
-
-                let checkpoint = CheckPoint {
-                    checked: (*boxed).checked,
-                    token: token_str,
-                };
-                Ok(checkpoint)
-            }
-        }
+    fn start_checkpoint(&self, mode: CheckpointMode) -> Result<CheckPoint, FasterError> {
+        checkpoint::start_checkpoint(self.faster_t, &self.config, &self.checkpoints, mode)
     }
 
     pub fn recover(
         &self,
-        index_token: String,
-        hybrid_log_token: String,
+        index_token: CheckpointToken,
+        hybrid_log_token: CheckpointToken,
     ) -> Result<Recover, FasterError> {
-        if self.storage_dir.is_none() {
+        if self.config.storage_dir.is_none() {
             return Err(FasterError::InvalidType);
         }
-        let index_token_c = CString::new(index_token).unwrap();
+        // A formatted GUID never contains a NUL byte
+        let index_token_c = CString::new(index_token.to_string()).unwrap();
         let index_token_ptr = index_token_c.into_raw();
 
-        let hybrid_token_c = CString::new(hybrid_log_token).unwrap();
+        let hybrid_token_c = CString::new(hybrid_log_token.to_string()).unwrap();
         let hybrid_token_ptr = hybrid_token_c.into_raw();
 
         let recover_result = unsafe {
@@ -248,57 +107,246 @@ impl FasterKv {
         match recover_result.is_null() {
             true => Err(FasterError::RecoveryError),
             false => {
-                let boxed = unsafe { Box::from_raw(recover_result) }; // makes sure mem is freed
-                let sessions_count = (*boxed).session_ids_count;
-                let mut session_ids_vec: Vec<String> = Vec::new();
-                for i in 0..sessions_count {
-                    let id = unsafe {
-                        CStr::from_ptr(((*boxed).session_ids).offset(37 * i as isize))
-                            .to_str()
-                            .unwrap()
-                            .to_owned()
-                    };
-                    session_ids_vec.push(id);
+                // The shim allocates the result and its session ids with malloc
+                let result = unsafe { &*recover_result };
+                let (status, version) = (Status::from(result.status), result.version);
+                // The ids are NUL-terminated strings with a stride of 37 bytes
+                let session_ids: Result<Vec<SessionId>, _> = (0..result.session_ids_count as usize)
+                    .map(|i| unsafe { CStr::from_ptr(result.session_ids.add(37 * i)) })
+                    .map(|id| id.to_str().ok().and_then(|id| id.parse().ok()))
+                    .map(|id| id.ok_or(FasterError::RecoveryError))
+                    .collect();
+                unsafe {
+                    libc::free(result.session_ids as *mut libc::c_void);
+                    libc::free(recover_result as *mut libc::c_void);
                 }
-                let recover = Recover {
-                    status: (*boxed).status,
-                    version: (*boxed).version,
-                    session_ids: session_ids_vec,
-                };
-                Ok(recover)
+                Ok(Recover {
+                    status,
+                    version,
+                    session_ids: session_ids?,
+                })
             }
         }
     }
 
-    pub fn complete_pending(&self, b: bool) -> () {
-        unsafe { ffi::faster_complete_pending(self.faster_t, b) }
+    /// Lists the checkpoints in the storage directory of the store, oldest first.
+    pub fn list_checkpoints(&self) -> Result<Vec<CheckpointInfo>, FasterError> {
+        match &self.config.storage_dir {
+            None => Err(FasterError::InvalidType),
+            Some(dir) => Ok(checkpoint::list_checkpoints(dir)?),
+        }
     }
 
-    pub fn start_session(&self) -> String {
-        unsafe {
-            let c_guid = ffi::faster_start_session(self.faster_t);
-            let rust_str = CStr::from_ptr(c_guid).to_str().unwrap().to_owned();
-            rust_str
+    /// Recovers from the newest checkpoint in the storage directory, pairing its hybrid log with
+    /// the newest index checkpoint it can be recovered with. Returns `RecoveryError` if there is
+    /// no such checkpoint.
+    pub fn recover_latest(&self) -> Result<Recover, FasterError> {
+        let checkpoints = self.list_checkpoints()?;
+        match checkpoint::latest_recoverable(&checkpoints) {
+            Some((index_token, hybrid_log_token)) => self.recover(index_token, hybrid_log_token),
+            None => Err(FasterError::RecoveryError),
         }
     }
 
-    pub fn continue_session(&self, token: String) -> u64 {
-        let token_str = CString::new(token).unwrap();
-        let token_ptr = token_str.into_raw();
-        unsafe {
-            let result = ffi::faster_continue_session(self.faster_t, token_ptr);
-            let _ = CString::from_raw(token_ptr);
-            result
+    /// Removes the index and hybrid log checkpoints with `token` from the storage directory.
+    /// Returns `CheckpointInUse` if `recover_latest` would recover from them, or if they are
+    /// still being persisted.
+    pub fn delete_checkpoint(&self, token: CheckpointToken) -> Result<(), FasterError> {
+        let storage_dir = match &self.config.storage_dir {
+            None => return Err(FasterError::InvalidType),
+            Some(dir) => dir,
+        };
+        let checkpoints = self.list_checkpoints()?;
+        let needed = checkpoint::latest_recoverable(&checkpoints)
+            .is_some_and(|(index, log)| index == token || log == token);
+        if needed || self.checkpoints.running() == Some(token) {
+            return Err(FasterError::CheckpointInUse);
+        }
+        let index = checkpoint::remove_checkpoint(storage_dir, token, CheckpointKind::Index)?;
+        let log = checkpoint::remove_checkpoint(storage_dir, token, CheckpointKind::HybridLog)?;
+        match index || log {
+            true => Ok(()),
+            false => Err(FasterError::IOError(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No such checkpoint",
+            ))),
         }
     }
 
-    pub fn stop_session(&self) -> () {
-        unsafe { ffi::faster_stop_session(self.faster_t) }
+    /// Removes the checkpoints that the retention policy of the store no longer keeps, see
+    /// `FasterKvBuilder::with_checkpoint_retention`. This is also done whenever a checkpoint is
+    /// started. Returns the removed checkpoints.
+    pub fn prune_checkpoints(&self) -> Result<Vec<CheckpointInfo>, FasterError> {
+        checkpoint::prune_checkpoints(&self.config, &self.checkpoints)
     }
 
-    pub fn refresh(&self) -> () {
-        unsafe {
-            ffi::faster_refresh_session(self.faster_t);
+    /// Starts a session on the current thread. Operations on the store are done through the
+    /// session, which is stopped when it is dropped.
+    ///
+    /// # Panics
+    /// Panics if the current thread already has an active session of this store.
+    pub fn session(&self) -> Session<C> {
+        Session::start(self)
+    }
+
+    /// Starts a session on the current thread whose operations are futures, see `AsyncSession`.
+    ///
+    /// # Panics
+    /// Panics if the current thread already has an active session of this store.
+    pub fn async_session(&self) -> AsyncSession<C> {
+        AsyncSession::new(Session::start(self))
+    }
+
+    /// Continues the recovered session `session_id`, see `Recover::session_ids`. Returns the
+    /// session together with the serial number of its last persisted operation, after which
+    /// the operations of the session have to be replayed.
+    ///
+    /// # Panics
+    /// Panics if the current thread already has an active session of this store.
+    pub fn resume_session(&self, session_id: SessionId) -> (Session<'_, C>, u64) {
+        let session = Session::resume(self, session_id);
+        let last_serial = session.last_serial();
+        (session, last_serial)
+    }
+
+    /// Like `resume_session`, but only returns the session. Its last persisted serial number is
+    /// still reported by `last_serial()`.
+    ///
+    /// # Panics
+    /// Panics if the current thread already has an active session of this store.
+    pub fn continue_session(&self, session_id: SessionId) -> Session<C> {
+        self.resume_session(session_id).0
+    }
+
+    /// Compacts the log below `until_address`: records that are still live are copied to the
+    /// tail of the log, after which the log is truncated at `until_address`. Returns false if
+    /// `until_address` is not between the begin and tail address, or the log could not be
+    /// truncated.
+    ///
+    /// Compaction runs on behalf of the session of the current thread, or a temporary session
+    /// if there is none. The storage below `until_address` is only released once every session
+    /// has refreshed. Each live key is copied with the latest value it has at the time of the
+    /// copy, so updates and deletes that happen while the compaction is running are kept. If
+    /// `until_address` lies in the mutable region, the log up to the tail is made read-only
+    /// first, which blocks until every session has refreshed.
+    pub fn compact(&self, until_address: u64) -> bool {
+        self.with_session(|| unsafe { ffi::faster_compact(self.faster_t, until_address) })
+    }
+
+    /// Truncates the log at `address`, dropping all records below it without copying them.
+    /// Like `compact`, this runs on behalf of the session of the current thread.
+    pub fn shift_begin_address(&self, address: u64) -> bool {
+        self.with_session(|| unsafe { ffi::faster_shift_begin_address(self.faster_t, address) })
+    }
+
+    /// The address of the oldest record in the log.
+    pub fn begin_address(&self) -> u64 {
+        unsafe { ffi::faster_get_log_addresses(self.faster_t).begin }
+    }
+
+    /// Records below this address are on disk only.
+    pub fn head_address(&self) -> u64 {
+        unsafe { ffi::faster_get_log_addresses(self.faster_t).head }
+    }
+
+    /// Records below this address are immutable and updated by copying them to the tail.
+    pub fn read_only_address(&self) -> u64 {
+        unsafe { ffi::faster_get_log_addresses(self.faster_t).read_only }
+    }
+
+    /// The address the next record is appended at.
+    pub fn tail_address(&self) -> u64 {
+        unsafe { ffi::faster_get_log_addresses(self.faster_t).tail }
+    }
+
+    /// Iterates over the live key-value pairs of the store, skipping deleted keys and
+    /// superseded versions. The iterator scans the log up to its tail at the time of the call,
+    /// so records written afterwards are not returned.
+    ///
+    /// The iterator is weakly consistent. Records in the mutable region are updated in place
+    /// without copying them, so a key updated while iterating may be returned with its old or
+    /// its new value, and a value that is overwritten while it is being read may come back
+    /// torn, i.e. fail to decode or mix both values. Use `snapshot_iter` when the store is
+    /// written to concurrently and a consistent view is needed.
+    ///
+    /// The scan runs on behalf of the session of the current thread, or a temporary session if
+    /// there is none.
+    ///
+    /// # Panics
+    /// The temporary session is held by the iterator, so a `session()` on the same thread panics
+    /// until the iterator is dropped. Start the session first to scan on its behalf instead.
+    pub fn iter<K, V>(&self) -> Iter<K, V, C>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        Iter::new(RawIter::new(self, false))
+    }
+
+    /// Like `iter`, but returns the encoded keys and values without decoding them. It is
+    /// weakly consistent like `iter`, see `snapshot_iter_raw` for a consistent view.
+    ///
+    /// # Panics
+    /// Like with `iter`, a `session()` on the same thread panics while the iterator holds a
+    /// temporary session.
+    pub fn iter_raw(&self) -> RawIter<C> {
+        RawIter::new(self, false)
+    }
+
+    /// Like `iter`, but first makes the log up to the tail read-only so that the iterator sees
+    /// the store exactly as it was when it was created. Blocks until every session has
+    /// refreshed.
+    ///
+    /// # Panics
+    /// Like with `iter`, a `session()` on the same thread panics while the iterator holds a
+    /// temporary session.
+    pub fn snapshot_iter<K, V>(&self) -> Iter<K, V, C>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        Iter::new(RawIter::new(self, true))
+    }
+
+    /// Like `snapshot_iter`, but returns the encoded keys and values without decoding them.
+    ///
+    /// # Panics
+    /// Like with `iter`, a `session()` on the same thread panics while the iterator holds a
+    /// temporary session.
+    pub fn snapshot_iter_raw(&self) -> RawIter<C> {
+        RawIter::new(self, true)
+    }
+
+    /// Returns every record version in `[from_address, to_address)` in log order, including
+    /// superseded versions and deletions, for consuming the changes to a store incrementally.
+    /// `from_address` has to be the address of a record, e.g. the `next_address` of the last
+    /// processed record or the `begin_address()` of the store. The range is limited to the
+    /// records that are still in the log.
+    ///
+    /// Records in the mutable region, above `read_only_address()`, are updated in place, so a
+    /// range ending above it may miss versions. Like `iter`, the scan runs on behalf of the
+    /// session of the current thread.
+    ///
+    /// # Panics
+    /// Like with `iter`, a `session()` on the same thread panics while the scan holds a
+    /// temporary session.
+    pub fn scan_range<K, V>(&self, from_address: u64, to_address: u64) -> RangeScan<K, V, C>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        RangeScan::new(self, from_address, to_address)
+    }
+
+    /// Runs `f` within the session of the current thread, starting a temporary one if the thread
+    /// has none.
+    fn with_session<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        let _session = self.thread_session();
+        f()
+    }
+
+    /// Starts a temporary session unless the current thread already has one on this store.
+    pub(crate) fn thread_session(&self) -> Option<Session<C>> {
+        match session::active_session_id(self.faster_t) {
+            Some(_) => None,
+            None => Some(Session::start(self)),
         }
     }
 
@@ -314,7 +362,7 @@ impl FasterKv {
 
     // Warning: Calling this will remove the stored data
     pub fn clean_storage(&self) -> Result<(), FasterError> {
-        match &self.storage_dir {
+        match &self.config.storage_dir {
             None => Err(FasterError::InvalidType),
             Some(dir) => {
                 fs::remove_dir_all(dir)?;
@@ -330,7 +378,7 @@ impl FasterKv {
     }
 }
 
-impl Default for FasterKv {
+impl Default for FasterKv<Bincode> {
     fn default() -> Self {
         FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024)
             .build()
@@ -339,11 +387,13 @@ impl Default for FasterKv {
 }
 
 // In order to make sure we release the resources the C interface has allocated for the store
-impl Drop for FasterKv {
+impl<C> Drop for FasterKv<C> {
     fn drop(&mut self) {
+        // The scheduler checkpoints the store until it is stopped
+        self.scheduler.take();
         self.destroy();
     }
 }
 
-unsafe impl Send for FasterKv {}
-unsafe impl Sync for FasterKv {}
+unsafe impl<C> Send for FasterKv<C> {}
+unsafe impl<C> Sync for FasterKv<C> {}
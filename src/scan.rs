@@ -0,0 +1,251 @@
+extern crate libfaster_sys as ffi;
+
+use crate::codec::Codec;
+use crate::session::Session;
+use crate::{FasterError, FasterKv};
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ptr;
+use std::slice;
+
+// How many records a scan reads before refreshing its session
+const REFRESH_INTERVAL: u64 = 1000;
+
+/// A record of the log as it is returned by a scan.
+struct ScanRecord<'r> {
+    // Position of the record in the scan, starting at 1
+    position: u64,
+    address: u64,
+    next_address: u64,
+    key: &'r [u8],
+    value: &'r [u8],
+    tombstone: bool,
+}
+
+/// Scans the log of a store in log order, including superseded versions and tombstones.
+struct LogScan<'a, C> {
+    store: &'a FasterKv<C>,
+    scan: *mut ffi::faster_scan_t,
+    record: ffi::faster_scan_record,
+    scanned: u64,
+}
+
+impl<'a, C> LogScan<'a, C> {
+    fn open(store: &'a FasterKv<C>, begin: u64, end: u64) -> LogScan<'a, C> {
+        let scan = unsafe { ffi::faster_scan_open(store.faster_t, begin, end) };
+        LogScan {
+            store,
+            scan,
+            record: ffi::faster_scan_record {
+                key: ptr::null(),
+                key_length: 0,
+                value: ptr::null(),
+                value_length: 0,
+                tombstone: false,
+                address: 0,
+                next_address: 0,
+            },
+            scanned: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<ScanRecord<'_>> {
+        if self.scanned > 0 && self.scanned.is_multiple_of(REFRESH_INTERVAL) {
+            unsafe { ffi::faster_refresh_session(self.store.faster_t) };
+        }
+        if !unsafe { ffi::faster_scan_next(self.scan, &mut self.record) } {
+            return None;
+        }
+        self.scanned += 1;
+        let record = &self.record;
+        unsafe {
+            Some(ScanRecord {
+                position: self.scanned,
+                address: record.address,
+                next_address: record.next_address,
+                key: slice::from_raw_parts(record.key, record.key_length as usize),
+                value: match record.tombstone {
+                    true => &[],
+                    false => slice::from_raw_parts(record.value, record.value_length as usize),
+                },
+                tombstone: record.tombstone,
+            })
+        }
+    }
+}
+
+impl<'a, C> Drop for LogScan<'a, C> {
+    fn drop(&mut self) {
+        unsafe { ffi::faster_scan_close(self.scan) }
+    }
+}
+
+/// Iterates over the encoded keys and values of the live records of a store, see
+/// `FasterKv::iter_raw`.
+///
+/// The records are scanned twice: the first pass finds the latest record of every key, the
+/// second yields those that are not deletions. Keeping the position of the latest record of
+/// every key takes memory proportional to the number of keys, but not their values.
+///
+/// Unless created by `snapshot_iter_raw`, the iterator reads records in the mutable region
+/// while they may be updated in place, see `FasterKv::iter` for what that means for its
+/// results.
+pub struct RawIter<'a, C> {
+    // Declared before the session, which has to outlive the scan
+    scan: Option<LogScan<'a, C>>,
+    _session: Option<Session<'a, C>>,
+    store: &'a FasterKv<C>,
+    end: u64,
+    latest: Option<HashMap<Vec<u8>, u64>>,
+}
+
+impl<'a, C> RawIter<'a, C> {
+    pub(crate) fn new(store: &'a FasterKv<C>, snapshot: bool) -> RawIter<'a, C> {
+        // Held until the iterator is dropped, so `store.session()` panics on this thread until then
+        let session = store.thread_session();
+        // Only a snapshot keeps sessions from updating the scanned records in place
+        let end = match snapshot {
+            true => unsafe { ffi::faster_shift_read_only_to_tail(store.faster_t) },
+            false => store.tail_address(),
+        };
+        RawIter {
+            scan: None,
+            _session: session,
+            store,
+            end,
+            latest: None,
+        }
+    }
+
+    /// The address up to which the log is scanned.
+    pub fn end_address(&self) -> u64 {
+        self.end
+    }
+
+    fn index(&self) -> HashMap<Vec<u8>, u64> {
+        let mut latest = HashMap::new();
+        let mut scan = LogScan::open(self.store, 0, self.end);
+        while let Some(record) = scan.next() {
+            match latest.get_mut(record.key) {
+                Some(position) => *position = record.position,
+                None => {
+                    latest.insert(record.key.to_vec(), record.position);
+                }
+            }
+        }
+        latest
+    }
+}
+
+impl<'a, C> Iterator for RawIter<'a, C> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<(Vec<u8>, Vec<u8>)> {
+        if self.latest.is_none() {
+            self.latest = Some(self.index());
+            self.scan = Some(LogScan::open(self.store, 0, self.end));
+        }
+        let latest = self.latest.as_ref().unwrap();
+        let scan = self.scan.as_mut().unwrap();
+        loop {
+            let record = scan.next()?;
+            if record.tombstone || latest.get(record.key) != Some(&record.position) {
+                continue;
+            }
+            return Some((record.key.to_vec(), record.value.to_vec()));
+        }
+    }
+}
+
+/// Iterates over the decoded keys and values of the live records of a store, see
+/// `FasterKv::iter`.
+pub struct Iter<'a, K, V, C> {
+    raw: RawIter<'a, C>,
+    types: PhantomData<(K, V)>,
+}
+
+impl<'a, K, V, C> Iter<'a, K, V, C> {
+    pub(crate) fn new(raw: RawIter<'a, C>) -> Iter<'a, K, V, C> {
+        Iter {
+            raw,
+            types: PhantomData,
+        }
+    }
+
+    /// The address up to which the log is scanned.
+    pub fn end_address(&self) -> u64 {
+        self.raw.end_address()
+    }
+}
+
+impl<'a, K, V, C> Iterator for Iter<'a, K, V, C>
+where
+    C: Codec<K> + Codec<V>,
+{
+    type Item = Result<(K, V), FasterError<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.raw.next()?;
+        let decoded = <C as Codec<K>>::decode(&key)
+            .and_then(|key| <C as Codec<V>>::decode(&value).map(|value| (key, value)));
+        Some(decoded)
+    }
+}
+
+/// A version of a record in the log, as returned by `FasterKv::scan_range`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord<K, V> {
+    /// The address of the record in the log.
+    pub address: u64,
+    /// The address right behind the record. A scan that continues from here returns the
+    /// records written after this one.
+    pub next_address: u64,
+    pub key: K,
+    /// The value written by an upsert or RMW, or `None` for a deletion.
+    pub value: Option<V>,
+}
+
+/// Iterates over every record version in a range of the log, see `FasterKv::scan_range`.
+pub struct RangeScan<'a, K, V, C> {
+    // Declared before the session, which has to outlive the scan
+    scan: LogScan<'a, C>,
+    _session: Option<Session<'a, C>>,
+    types: PhantomData<(K, V)>,
+}
+
+impl<'a, K, V, C> RangeScan<'a, K, V, C> {
+    pub(crate) fn new(store: &'a FasterKv<C>, from_address: u64, to_address: u64) -> RangeScan<'a, K, V, C> {
+        // Held like the session of a RawIter
+        let session = store.thread_session();
+        RangeScan {
+            scan: LogScan::open(store, from_address, to_address),
+            _session: session,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, C> Iterator for RangeScan<'a, K, V, C>
+where
+    C: Codec<K> + Codec<V>,
+{
+    type Item = Result<LogRecord<K, V>, FasterError<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.scan.next()?;
+        let value = match record.tombstone {
+            true => Ok(None),
+            false => <C as Codec<V>>::decode(record.value).map(Some),
+        };
+        let decoded = <C as Codec<K>>::decode(record.key).and_then(|key| {
+            Ok(LogRecord {
+                address: record.address,
+                next_address: record.next_address,
+                key,
+                value: value?,
+            })
+        });
+        Some(decoded)
+    }
+}
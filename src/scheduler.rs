@@ -0,0 +1,208 @@
+extern crate libfaster_sys as ffi;
+
+use crate::builder::FasterKvConfig;
+use crate::checkpoint::{self, CheckpointMode, CheckpointTracker, PersistedCheckpoint};
+use crate::{FasterError, FasterKv};
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+type Report = Arc<dyn Fn(Result<PersistedCheckpoint, FasterError<'static>>) + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trigger {
+    Interval(Duration),
+    Operations(u64),
+}
+
+/// Takes checkpoints of a store in the background, see
+/// `FasterKvBuilder::with_checkpoint_scheduler`.
+///
+/// A checkpoint is taken at a fixed interval or after a number of operations, which counts the
+/// operations of all sessions of the store. Like any checkpoint, it is only persisted once the
+/// sessions of the store have refreshed. The result of every checkpoint is reported to the
+/// callback or channel of the scheduler, except for a checkpoint that is still being persisted
+/// when the store is dropped.
+#[derive(Clone)]
+pub struct CheckpointScheduler {
+    trigger: Trigger,
+    mode: CheckpointMode,
+    report: Option<Report>,
+}
+
+impl CheckpointScheduler {
+    /// Takes a full checkpoint every `interval`, counted from when the previous one was persisted.
+    pub fn every(interval: Duration) -> CheckpointScheduler {
+        CheckpointScheduler::new(Trigger::Interval(interval))
+    }
+
+    /// Takes a full checkpoint after every `count` operations on the store.
+    pub fn after_operations(count: u64) -> CheckpointScheduler {
+        CheckpointScheduler::new(Trigger::Operations(count))
+    }
+
+    fn new(trigger: Trigger) -> CheckpointScheduler {
+        CheckpointScheduler {
+            trigger,
+            mode: CheckpointMode::Full,
+            report: None,
+        }
+    }
+
+    /// Takes index-only or hybrid-log-only checkpoints instead of full ones.
+    pub fn with_mode(mut self, mode: CheckpointMode) -> CheckpointScheduler {
+        self.mode = mode;
+        self
+    }
+
+    /// Calls `report` on the scheduler's thread with the result of every checkpoint.
+    pub fn on_checkpoint<F>(mut self, report: F) -> CheckpointScheduler
+    where
+        F: Fn(Result<PersistedCheckpoint, FasterError<'static>>) + Send + Sync + 'static,
+    {
+        self.report = Some(Arc::new(report));
+        self
+    }
+
+    /// Sends the result of every checkpoint to `sender`.
+    pub fn report_to(self, sender: Sender<Result<PersistedCheckpoint, FasterError<'static>>>) -> CheckpointScheduler {
+        let sender = Mutex::new(sender);
+        self.on_checkpoint(move |result| {
+            // The receiver may have been dropped, which stops reporting but not checkpointing
+            let _ = sender.lock().unwrap().send(result);
+        })
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        match self.trigger {
+            Trigger::Interval(interval) => interval > Duration::from_secs(0),
+            Trigger::Operations(count) => count > 0,
+        }
+    }
+}
+
+// State shared between a store and the thread of its scheduler
+struct Shared {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    operations: AtomicU64,
+}
+
+impl Shared {
+    /// Blocks until the next checkpoint is due. Returns false once the scheduler is stopped.
+    fn wait_for_trigger(&self, trigger: Trigger) -> bool {
+        let mut stopped = self.stopped.lock().unwrap();
+        let started = Instant::now();
+        loop {
+            if *stopped {
+                return false;
+            }
+            stopped = match trigger {
+                Trigger::Interval(interval) => {
+                    let elapsed = started.elapsed();
+                    if elapsed >= interval {
+                        return true;
+                    }
+                    self.wake.wait_timeout(stopped, interval - elapsed).unwrap().0
+                }
+                Trigger::Operations(count) => {
+                    if self.operations.load(Ordering::Relaxed) >= count {
+                        self.operations.store(0, Ordering::Relaxed);
+                        return true;
+                    }
+                    self.wake.wait(stopped).unwrap()
+                }
+            };
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+}
+
+// The store a scheduler checkpoints, which stops the scheduler before it is destroyed
+struct StorePtr(*mut ffi::faster_t);
+
+unsafe impl Send for StorePtr {}
+
+/// The running scheduler of a store. Dropping it stops the scheduler and waits for its thread.
+pub(crate) struct SchedulerHandle {
+    shared: Arc<Shared>,
+    every_operations: Option<u64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    pub(crate) fn spawn<C>(scheduler: CheckpointScheduler, store: &FasterKv<C>) -> io::Result<SchedulerHandle> {
+        let shared = Arc::new(Shared {
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+            operations: AtomicU64::new(0),
+        });
+        let every_operations = match scheduler.trigger {
+            Trigger::Operations(count) => Some(count),
+            Trigger::Interval(_) => None,
+        };
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let faster_t = StorePtr(store.faster_t);
+            let config = store.config.clone();
+            let tracker = Arc::clone(&store.checkpoints);
+            thread::Builder::new()
+                .name(String::from("faster-checkpoint"))
+                .spawn(move || run(&scheduler, &shared, faster_t, &config, &tracker))?
+        };
+        Ok(SchedulerHandle {
+            shared,
+            every_operations,
+            thread: Some(thread),
+        })
+    }
+
+    /// Counts an operation of a session towards the next checkpoint.
+    pub(crate) fn count_operation(&self) {
+        if let Some(count) = self.every_operations {
+            if self.shared.operations.fetch_add(1, Ordering::Relaxed) + 1 == count {
+                let _stopped = self.shared.stopped.lock().unwrap();
+                self.shared.wake.notify_one();
+            }
+        }
+    }
+}
+
+impl Drop for SchedulerHandle {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            // A panic of the report callback has already been printed by the thread
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    scheduler: &CheckpointScheduler,
+    shared: &Shared,
+    faster_t: StorePtr,
+    config: &FasterKvConfig,
+    tracker: &CheckpointTracker,
+) {
+    while shared.wait_for_trigger(scheduler.trigger) {
+        let result = match checkpoint::start_checkpoint(faster_t.0, config, tracker, scheduler.mode) {
+            Ok(checkpoint) => match checkpoint.wait_unless(|| shared.is_stopped()) {
+                Some(result) => result,
+                None => return,
+            },
+            Err(err) => Err(err),
+        };
+        if let Some(report) = &scheduler.report {
+            report(result);
+        }
+    }
+}
@@ -0,0 +1,1029 @@
+extern crate libc;
+extern crate libfaster_sys as ffi;
+
+use crate::backoff::Backoff;
+use crate::codec::{Codec, Encode};
+use crate::faster_traits::{
+    completion_callback, conditional_callback, multi_get_callback, read_callback, read_option_callback,
+    read_with_callback, read_with_relocate, rmw_callback, rmw_with_callback, rmw_with_done,
+    rmw_with_relocate, take_callback_error, Completed, MultiGetSlot, ReadWithState, RmwWithState,
+};
+use crate::guid::SessionId;
+use crate::util::{Lookup, Swap};
+use crate::{Bincode, FasterError, FasterKv, FasterRmw, Status};
+
+use futures_core::Stream;
+use local_channel::mpsc::{channel, Receiver, Sender};
+use log::error;
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::future::poll_fn;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// Decodes the outcome of a conditional write
+type SwapReader<V> = fn(Option<&[u8]>, bool) -> Result<Swap<V>, FasterError<'static>>;
+
+thread_local! {
+    // FASTER keeps the session of a thread in thread-local state of the store, so a thread can
+    // run one session per store at a time. Holds the ids of those sessions by store.
+    static ACTIVE_SESSIONS: RefCell<Vec<(*mut ffi::faster_t, SessionId)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The id of the session of `faster_t` running on the current thread, if any.
+pub(crate) fn active_session_id(faster_t: *mut ffi::faster_t) -> Option<SessionId> {
+    ACTIVE_SESSIONS.with(|active| {
+        active
+            .borrow()
+            .iter()
+            .find(|(store, _)| *store == faster_t)
+            .map(|(_, id)| *id)
+    })
+}
+
+/// The ids of the sessions of all stores running on the current thread.
+pub(crate) fn active_session_ids() -> Vec<SessionId> {
+    ACTIVE_SESSIONS.with(|active| active.borrow().iter().map(|(_, id)| *id).collect())
+}
+
+/// A FASTER session on the current thread. All operations on a store go through a session.
+///
+/// The session numbers its operations with monotonic serial numbers, which FASTER uses to
+/// report up to which operation a session was persisted by a checkpoint. The session is stopped
+/// when dropped. As FASTER binds sessions to the thread that started them, a `Session` is
+/// neither `Send` nor `Sync`.
+pub struct Session<'a, C = Bincode> {
+    store: &'a FasterKv<C>,
+    id: SessionId,
+    serial: Cell<u64>,
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl<'a, C> Session<'a, C> {
+    pub(crate) fn start(store: &'a FasterKv<C>) -> Session<'a, C> {
+        claim_thread(store.faster_t);
+        let mut guid = [0u8; ffi::FASTER_GUID_SIZE as usize];
+        let started = unsafe {
+            ffi::faster_start_session(store.faster_t, guid.as_mut_ptr() as *mut libc::c_char)
+        };
+        let id = CStr::from_bytes_until_nul(&guid)
+            .ok()
+            .filter(|_| started)
+            .and_then(|id| id.to_str().ok())
+            .and_then(|id| id.parse().ok())
+            .expect("FASTER returned a malformed session id");
+        Session::new(store, id, 0)
+    }
+
+    pub(crate) fn resume(store: &'a FasterKv<C>, id: SessionId) -> Session<'a, C> {
+        claim_thread(store.faster_t);
+        // A formatted GUID never contains a NUL byte
+        let token_str = CString::new(id.to_string()).unwrap();
+        let token_ptr = token_str.into_raw();
+        let recovered_serial = unsafe {
+            let result = ffi::faster_continue_session(store.faster_t, token_ptr);
+            let _ = CString::from_raw(token_ptr);
+            result
+        };
+        Session::new(store, id, recovered_serial)
+    }
+
+    fn new(store: &'a FasterKv<C>, id: SessionId, serial: u64) -> Session<'a, C> {
+        ACTIVE_SESSIONS.with(|active| active.borrow_mut().push((store.faster_t, id)));
+        store.checkpoints.register_session(id);
+        Session {
+            store,
+            id,
+            serial: Cell::new(serial),
+            _thread_bound: PhantomData,
+        }
+    }
+
+    /// The id of the session, which is used to continue it after recovery.
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// The serial number of the last operation of this session. For a continued session, this
+    /// is the serial number of the last operation that was recovered until more operations are
+    /// done.
+    pub fn last_serial(&self) -> u64 {
+        self.serial.get()
+    }
+
+    /// Sets the serial number of the last operation, so that the next operation is numbered
+    /// `serial + 1`. This is only needed when serial numbers have to line up with an external
+    /// sequence, e.g. the offsets of a replayed log.
+    ///
+    /// # Panics
+    /// Panics if `serial` is lower than `last_serial()`, as serial numbers must be monotonic.
+    pub fn set_serial(&self, serial: u64) {
+        assert!(
+            serial >= self.serial.get(),
+            "Serial numbers must be monotonic, {} is lower than {}",
+            serial,
+            self.serial.get()
+        );
+        self.serial.set(serial);
+    }
+
+    fn next_serial(&self) -> u64 {
+        if let Some(scheduler) = &self.store.scheduler {
+            scheduler.count_operation();
+        }
+        let serial = self.serial.get() + 1;
+        self.serial.set(serial);
+        serial
+    }
+
+    pub fn upsert<K, V>(&self, key: &K, value: &V) -> Result<Status, FasterError>
+    where
+        C: Encode<K> + Encode<V>,
+        K: ?Sized,
+        V: ?Sized,
+    {
+        self.issue_upsert(key, value, Completion::none())
+    }
+
+    fn issue_upsert<K, V>(&self, key: &K, value: &V, completion: Completion) -> Result<Status, FasterError>
+    where
+        C: Encode<K> + Encode<V>,
+        K: ?Sized,
+        V: ?Sized,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = <C as Encode<K>>::encode(key, &mut key_buf)?;
+        let mut value_buf = Vec::new();
+        let encoded_value = <C as Encode<V>>::encode(value, &mut value_buf)?;
+        let status = unsafe {
+            ffi::faster_upsert(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                encoded_value.as_ptr(),
+                encoded_value.len() as u64,
+                self.next_serial(),
+                completion.callback(),
+                completion.target(),
+            )
+        };
+        completion.issued(status);
+        Status::from(status).into_result()
+    }
+
+    /// Reads the value of `key`, which is sent to the returned receiver. The receiver closes
+    /// without a value if the key does not exist, and yields an error if the value could not
+    /// be decoded or FASTER failed to complete a pending read.
+    pub fn read<K, V>(
+        &self,
+        key: &K,
+    ) -> Result<(Status, Receiver<Result<V, FasterError<'static>>>), FasterError>
+    where
+        C: Encode<K> + Codec<V>,
+        K: ?Sized,
+    {
+        self.issue_read(key, read_callback::<C, V>)
+    }
+
+    /// Issues a read whose `callback` sends its result to the `Sender<R>` it is called with.
+    fn issue_read<K, R>(
+        &self,
+        key: &K,
+        callback: unsafe extern "C" fn(*mut libc::c_void, *const u8, u64, ffi::faster_status),
+    ) -> Result<(Status, Receiver<R>), FasterError>
+    where
+        C: Encode<K>,
+        K: ?Sized,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = <C as Encode<K>>::encode(key, &mut key_buf)?;
+        let (sender, receiver) = channel();
+        let sender_ptr: *mut Sender<R> = Box::into_raw(Box::new(sender));
+        let status = unsafe {
+            ffi::faster_read(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                self.next_serial(),
+                Some(callback),
+                sender_ptr as *mut libc::c_void,
+            )
+        };
+        Status::from(status).into_result().map(|status| (status, receiver))
+    }
+
+    /// Reads the record of `key` and passes its encoded bytes to `reader`, without copying or
+    /// decoding them.
+    ///
+    /// The reader runs before `read_with` returns when the record is in memory. Otherwise the
+    /// read goes pending, and the reader runs on this thread once `complete_pending` completes it.
+    /// The reader must not panic, as it is called from FASTER.
+    ///
+    /// # Example
+    /// ```
+    /// use faster_rs::{FasterKv, Lookup};
+    /// let store = FasterKv::default();
+    /// let session = store.session();
+    /// let key = 1u64;
+    /// session.upsert(&key, &42u64).unwrap();
+    ///
+    /// match session.read_with(&key, |bytes: &[u8]| bytes.len()).unwrap() {
+    ///     Lookup::Found(length) => assert_eq!(length, 8),
+    ///     _ => panic!("Record should be in memory"),
+    /// }
+    /// ```
+    pub fn read_with<K, F, R>(&self, key: &K, reader: F) -> Result<Lookup<R>, FasterError>
+    where
+        C: Encode<K>,
+        K: ?Sized,
+        F: FnOnce(&[u8]) -> R + 'static,
+        R: 'static,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = <C as Encode<K>>::encode(key, &mut key_buf)?;
+        let mut state = ReadWithState::new(reader);
+        let status = unsafe {
+            ffi::faster_read_with(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                self.next_serial(),
+                Some(read_with_callback::<F, R>),
+                Some(read_with_relocate::<F, R>),
+                &mut state as *mut ReadWithState<F, R> as *mut libc::c_void,
+            )
+        };
+        if let Some(err) = take_callback_error() {
+            return Err(err);
+        }
+        let lookup = match Status::from(status).into_result()? {
+            Status::Pending => state.receiver.take().map(Lookup::Pending),
+            _ => state.result.take().map(Lookup::Found),
+        };
+        Ok(lookup.unwrap_or(Lookup::NotFound))
+    }
+
+    pub fn rmw<K, V>(&self, key: &K, value: &V) -> Result<Status, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        V: FasterRmw,
+    {
+        self.issue_rmw(key, value, Completion::none())
+    }
+
+    fn issue_rmw<K, V>(&self, key: &K, value: &V, completion: Completion) -> Result<Status, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        V: FasterRmw,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = C::encode(key, &mut key_buf)?;
+        let mut value_buf = Vec::new();
+        let encoded_value = C::encode(value, &mut value_buf)?;
+        let status = unsafe {
+            ffi::faster_rmw(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                encoded_value.as_ptr(),
+                encoded_value.len() as u64,
+                self.next_serial(),
+                Some(rmw_callback::<C, V>),
+                completion.callback(),
+                completion.target(),
+            )
+        };
+        completion.issued(status);
+        if let Some(err) = take_callback_error() {
+            return Err(err);
+        }
+        Status::from(status).into_result()
+    }
+
+    /// Read-Modify-Write with closures instead of `FasterRmw`, so that the same value type can be
+    /// merged differently by different calls. `update` computes the new value from the current
+    /// one, and `initial` computes the value of a key that has no record yet.
+    ///
+    /// `update` may be called more than once, e.g. when FASTER retries the update of a record
+    /// that another thread updated at the same time. When the record has to be fetched from disk,
+    /// the closures run once `complete_pending` completes the RMW. They must not panic, as they
+    /// are called from FASTER.
+    ///
+    /// The record of a key without one is only created once the value from `initial` has been
+    /// encoded, so the key stays absent if that fails. If the value from `update` cannot be
+    /// encoded, the record keeps its current value. Either way the error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use faster_rs::FasterKv;
+    /// let store = FasterKv::default();
+    /// let session = store.session();
+    /// let key = 1u64;
+    ///
+    /// // Keeps the maximum instead of adding up like `rmw` does for u64
+    /// session.rmw_with(&key, || 5u64, |current: &u64| (*current).max(5)).unwrap();
+    /// session.rmw_with(&key, || 3u64, |current: &u64| (*current).max(3)).unwrap();
+    /// ```
+    pub fn rmw_with<K, V, I, U>(&self, key: &K, initial: I, update: U) -> Result<Status, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        I: FnOnce() -> V + 'static,
+        U: Fn(&V) -> V + 'static,
+    {
+        self.issue_rmw_with(key, initial, update, None)
+    }
+
+    fn issue_rmw_with<K, V, I, U>(
+        &self,
+        key: &K,
+        initial: I,
+        update: U,
+        done: Option<Sender<Completed>>,
+    ) -> Result<Status, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        I: FnOnce() -> V + 'static,
+        U: Fn(&V) -> V + 'static,
+    {
+        let initial = move || {
+            let mut buf = Vec::new();
+            Ok(<C as Codec<V>>::encode(&initial(), &mut buf)?.to_vec())
+        };
+        let update = move |current: &[u8]| {
+            let updated = update(&<C as Codec<V>>::decode(current)?);
+            let mut buf = Vec::new();
+            Ok(<C as Codec<V>>::encode(&updated, &mut buf)?.to_vec())
+        };
+        self.rmw_with_encoded(key, initial, update, done)
+    }
+
+    fn rmw_with_encoded<K, I, U>(
+        &self,
+        key: &K,
+        initial: I,
+        update: U,
+        done: Option<Sender<Completed>>,
+    ) -> Result<Status, FasterError>
+    where
+        C: Codec<K>,
+        I: FnOnce() -> Result<Vec<u8>, FasterError<'static>>,
+        U: Fn(&[u8]) -> Result<Vec<u8>, FasterError<'static>>,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = C::encode(key, &mut key_buf)?;
+        let mut state = RmwWithState::new(initial, update, done);
+        let status = unsafe {
+            ffi::faster_rmw_with(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                self.next_serial(),
+                Some(rmw_with_callback::<I, U>),
+                Some(rmw_with_relocate::<I, U>),
+                Some(rmw_with_done::<I, U>),
+                &mut state as *mut RmwWithState<I, U> as *mut libc::c_void,
+            )
+        };
+        if let Some(err) = take_callback_error() {
+            return Err(err);
+        }
+        Status::from(status).into_result()
+    }
+
+    /// Writes `new` to `key` if its current value is equal to `expected`, for optimistic
+    /// concurrency control. Values are compared by their encoded bytes, so the codec has to
+    /// encode equal values to the same bytes.
+    ///
+    /// Returns whether `new` was written, along with the value before the operation. Nothing is
+    /// written if `key` has no record, which returns `Lookup::NotFound`. Like `read_with`, the
+    /// outcome is sent to a receiver once `complete_pending` completes the operation if the
+    /// record has to be fetched from disk.
+    ///
+    /// The write is an RMW, which FASTER cannot abort once it copies a record that lies below
+    /// the read-only address to the tail. If `expected` does not match such a record, the value
+    /// stays the same, but an unchanged copy of the record is still appended to the log.
+    ///
+    /// # Example
+    /// ```
+    /// use faster_rs::{FasterKv, Lookup, Swap};
+    /// let store = FasterKv::default();
+    /// let session = store.session();
+    /// let key = 1u64;
+    /// session.upsert(&key, &1u64).unwrap();
+    ///
+    /// match session.compare_and_swap(&key, &1u64, &2u64).unwrap() {
+    ///     Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: true, current: Some(1u64) }),
+    ///     _ => panic!("Record should be in memory"),
+    /// }
+    /// match session.compare_and_swap(&key, &1u64, &3u64).unwrap() {
+    ///     Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: false, current: Some(2u64) }),
+    ///     _ => panic!("Record should be in memory"),
+    /// }
+    /// ```
+    pub fn compare_and_swap<K, V>(
+        &self,
+        key: &K,
+        expected: &V,
+        new: &V,
+    ) -> Result<Lookup<Swap<V>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = C::encode(key, &mut key_buf)?;
+        let mut expected_buf = Vec::new();
+        let encoded_expected = C::encode(expected, &mut expected_buf)?;
+        let mut value_buf = Vec::new();
+        let encoded_value = C::encode(new, &mut value_buf)?;
+        let mut state = ReadWithState::new(decode_swap::<C, V> as SwapReader<V>);
+        let status = unsafe {
+            ffi::faster_compare_and_swap(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                encoded_expected.as_ptr(),
+                encoded_expected.len() as u64,
+                encoded_value.as_ptr(),
+                encoded_value.len() as u64,
+                self.next_serial(),
+                Some(conditional_callback::<SwapReader<V>, Swap<V>>),
+                Some(read_with_relocate::<SwapReader<V>, Swap<V>>),
+                &mut state as *mut ReadWithState<SwapReader<V>, Swap<V>> as *mut libc::c_void,
+            )
+        };
+        conditional_lookup(status, state)
+    }
+
+    /// Writes `value` to `key` only if `key` has no record.
+    ///
+    /// Returns whether `value` was written, along with the existing value if it was not. Like
+    /// `compare_and_swap`, the outcome may be pending, and an existing record below the read-only
+    /// address is copied to the tail unchanged.
+    pub fn insert_if_absent<K, V>(&self, key: &K, value: &V) -> Result<Lookup<Swap<V>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = C::encode(key, &mut key_buf)?;
+        let mut value_buf = Vec::new();
+        let encoded_value = C::encode(value, &mut value_buf)?;
+        let mut state = ReadWithState::new(decode_swap::<C, V> as SwapReader<V>);
+        let status = unsafe {
+            ffi::faster_insert_if_absent(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                encoded_value.as_ptr(),
+                encoded_value.len() as u64,
+                self.next_serial(),
+                Some(conditional_callback::<SwapReader<V>, Swap<V>>),
+                Some(read_with_relocate::<SwapReader<V>, Swap<V>>),
+                &mut state as *mut ReadWithState<SwapReader<V>, Swap<V>> as *mut libc::c_void,
+            )
+        };
+        conditional_lookup(status, state)
+    }
+
+    /// Deletes a previously inserted key.
+    ///
+    /// Returns [Status::NotFound](enum.Status.html#variant.NotFound) for un-inserted keys.
+    ///
+    /// # Example
+    /// ```
+    /// use monoio::{IoUringDriver};
+    /// use faster_rs::{FasterKv, Status};
+    /// let store = FasterKv::default();
+    /// monoio::start::<IoUringDriver, _>(async move {
+    ///   let session = store.session();
+    ///   let key = 1;
+    ///   let value = 42;
+    ///
+    ///   // Insert key-value
+    ///   session.upsert(&key, &value).unwrap();
+    ///
+    ///   // Read key-value
+    ///   let (res, mut recv) = session.read(&key).unwrap();
+    ///   assert_eq!(Status::Ok, res);
+    ///   assert_eq!(value, recv.recv().await.unwrap().unwrap());
+    ///
+    ///   // Delete key-value
+    ///   session.delete(&key).unwrap();
+    ///
+    ///   // Re-read key-value and confirm deleted
+    ///   let (res, mut recv) = session.read::<i32, i32>(&key).unwrap();
+    ///   assert_eq!(Status::NotFound, res);
+    ///   assert!(recv.recv().await.is_none());
+    /// });
+    /// ```
+    pub fn delete<K>(&self, key: &K) -> Result<Status, FasterError>
+    where
+        C: Encode<K>,
+        K: ?Sized,
+    {
+        self.issue_delete(key, Completion::none())
+    }
+
+    fn issue_delete<K>(&self, key: &K, completion: Completion) -> Result<Status, FasterError>
+    where
+        C: Encode<K>,
+        K: ?Sized,
+    {
+        let mut key_buf = Vec::new();
+        let encoded_key = <C as Encode<K>>::encode(key, &mut key_buf)?;
+        let status = unsafe {
+            ffi::faster_delete(
+                self.store.faster_t,
+                encoded_key.as_ptr(),
+                encoded_key.len() as u64,
+                self.next_serial(),
+                completion.callback(),
+                completion.target(),
+            )
+        };
+        completion.issued(status);
+        Status::from(status).into_result()
+    }
+
+    /// Reads the values of `keys`, in the order of the keys. A key without a record reads as
+    /// `None`, and a key or value that fails to (de)serialize only fails its own read.
+    ///
+    /// All reads are issued before any of them is waited for, and reads that go pending are
+    /// completed by a single `complete_pending` call. The batch shares one refresh of the session
+    /// and one serialization buffer, and needs no channels. Returns an error if completing the
+    /// pending operations of the session raised one.
+    ///
+    /// # Example
+    /// ```
+    /// use faster_rs::FasterKv;
+    /// let store = FasterKv::default();
+    /// let session = store.session();
+    /// session.multi_upsert(&[(1u64, 10u64), (2u64, 20u64)]).unwrap();
+    ///
+    /// let values: Vec<Option<u64>> = session
+    ///     .multi_get(&[1u64, 2, 3])
+    ///     .unwrap()
+    ///     .into_iter()
+    ///     .map(Result::unwrap)
+    ///     .collect();
+    /// assert_eq!(values, vec![Some(10), Some(20), None]);
+    /// ```
+    pub fn multi_get<K, V>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Result<Option<V>, FasterError<'static>>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut batch = self.issue_multi_get(keys);
+        if batch.pending {
+            batch.pending = false;
+            self.complete_pending(true)?;
+        }
+        Ok(batch.into_results())
+    }
+
+    fn issue_multi_get<K, V>(&self, keys: &[K]) -> MultiGet<'_, 'a, C, V>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut batch = MultiGet {
+            session: self,
+            slots: keys.iter().map(|_| None).collect(),
+            pending: false,
+        };
+        // The slots are not moved while FASTER holds pointers to them
+        let slots = batch.slots.as_mut_ptr();
+        let mut key_buf = Vec::new();
+        self.refresh();
+        for (i, key) in keys.iter().enumerate() {
+            let slot = unsafe { slots.add(i) };
+            let encoded_key = match C::encode(key, &mut key_buf) {
+                Ok(encoded_key) => encoded_key,
+                Err(err) => {
+                    unsafe { *slot = Some(Err(err)) };
+                    continue;
+                }
+            };
+            let status = unsafe {
+                ffi::faster_read_with(
+                    self.store.faster_t,
+                    encoded_key.as_ptr(),
+                    encoded_key.len() as u64,
+                    self.next_serial(),
+                    Some(multi_get_callback::<C, V>),
+                    None,
+                    slot as *mut libc::c_void,
+                )
+            };
+            match Status::from(status) {
+                Status::Pending => batch.pending = true,
+                // The callback only runs for reads that FASTER accepted
+                status if status.is_error() => unsafe {
+                    *slot = Some(Err(FasterError::OperationError(status)));
+                },
+                _ => {}
+            }
+        }
+        batch
+    }
+
+    /// Upserts `entries` in order, sharing one refresh of the session and the serialization
+    /// buffers. Upserts that go pending are completed by a single `complete_pending` call at the
+    /// end.
+    ///
+    /// Stops at the first entry that fails to serialize or to be upserted, in which case the
+    /// entries before it have been upserted.
+    pub fn multi_upsert<K, V>(&self, entries: &[(K, V)]) -> Result<(), FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        if self.issue_multi_upsert(entries, false)?.0 {
+            self.complete_pending(true)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether any of the upserts went pending, and the receivers that are notified when
+    /// they complete if `notify` is set.
+    fn issue_multi_upsert<K, V>(
+        &self,
+        entries: &[(K, V)],
+        notify: bool,
+    ) -> Result<(bool, Vec<Receiver<Completed>>), FasterError<'static>>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut key_buf = Vec::new();
+        let mut value_buf = Vec::new();
+        let mut pending = false;
+        let mut receivers = Vec::new();
+        self.refresh();
+        for (key, value) in entries {
+            let encoded_key = C::encode(key, &mut key_buf)?;
+            let encoded_value = C::encode(value, &mut value_buf)?;
+            let (completion, receiver) = match notify {
+                true => Completion::new(),
+                false => (Completion::none(), None),
+            };
+            let status = unsafe {
+                ffi::faster_upsert(
+                    self.store.faster_t,
+                    encoded_key.as_ptr(),
+                    encoded_key.len() as u64,
+                    encoded_value.as_ptr(),
+                    encoded_value.len() as u64,
+                    self.next_serial(),
+                    completion.callback(),
+                    completion.target(),
+                )
+            };
+            completion.issued(status);
+            if Status::from(status).into_result()? == Status::Pending {
+                pending = true;
+                receivers.extend(receiver);
+            }
+        }
+        Ok((pending, receivers))
+    }
+
+    /// Reports the progress of this session to FASTER, which is needed for checkpoints and
+    /// index growth to complete.
+    pub fn refresh(&self) {
+        unsafe {
+            ffi::faster_refresh_session(self.store.faster_t);
+        }
+    }
+
+    /// Completes pending operations of this session, waiting for all of them when `wait` is
+    /// set. Returns whether no pending operations are left.
+    ///
+    /// Returns the first error raised while completing operations that have no receiver of
+    /// their own, e.g. a `rmw` whose value could not be deserialized. Reads and conditional
+    /// writes send their errors to their receivers instead.
+    pub fn complete_pending(&self, wait: bool) -> Result<bool, FasterError> {
+        let completed = unsafe { ffi::faster_complete_pending(self.store.faster_t, wait) };
+        match take_callback_error() {
+            Some(err) => Err(err),
+            None => Ok(completed),
+        }
+    }
+}
+
+impl<'a, C> Drop for Session<'a, C> {
+    fn drop(&mut self) {
+        if let Err(err) = self.complete_pending(true) {
+            error!("Error completing pending operations of session {}: {}", self.id, err);
+        }
+        unsafe { ffi::faster_stop_session(self.store.faster_t) }
+        self.store.checkpoints.unregister_session(self.id);
+        let faster_t = self.store.faster_t;
+        ACTIVE_SESSIONS.with(|active| active.borrow_mut().retain(|(store, _)| *store != faster_t));
+    }
+}
+
+/// The reads of a `multi_get`, which FASTER writes the results of into `slots`. Reads that went
+/// pending are completed before the slots are dropped, e.g. when the future of
+/// `AsyncSession::multi_get` is dropped.
+struct MultiGet<'s, 'a, C, V> {
+    session: &'s Session<'a, C>,
+    slots: Vec<MultiGetSlot<V>>,
+    pending: bool,
+}
+
+impl<'s, 'a, C, V> MultiGet<'s, 'a, C, V> {
+    fn into_results(mut self) -> Vec<Result<Option<V>, FasterError<'static>>> {
+        std::mem::take(&mut self.slots)
+            .into_iter()
+            .map(|slot| slot.unwrap_or(Err(FasterError::OperationError(Status::Pending))))
+            .collect()
+    }
+}
+
+impl<'s, 'a, C, V> Drop for MultiGet<'s, 'a, C, V> {
+    fn drop(&mut self) {
+        if self.pending {
+            if let Err(err) = self.session.complete_pending(true) {
+                error!("Error completing pending reads of session {}: {}", self.session.id, err);
+            }
+        }
+    }
+}
+
+fn claim_thread(faster_t: *mut ffi::faster_t) {
+    if active_session_id(faster_t).is_some() {
+        panic!("A FASTER session of this store is already active on this thread");
+    }
+}
+
+/// Notifies a receiver once an upsert, RMW or delete that went pending has been completed by
+/// `complete_pending`. The operations of `Session` are not notified, and only those of
+/// `AsyncSession` pay for the channel.
+struct Completion {
+    done: *mut Sender<Completed>,
+}
+
+impl Completion {
+    fn none() -> Completion {
+        Completion { done: std::ptr::null_mut() }
+    }
+
+    fn new() -> (Completion, Option<Receiver<Completed>>) {
+        let (sender, receiver) = channel();
+        let done = Box::into_raw(Box::new(sender));
+        (Completion { done }, Some(receiver))
+    }
+
+    fn callback(&self) -> ffi::completion_callback {
+        match self.done.is_null() {
+            true => None,
+            false => Some(completion_callback),
+        }
+    }
+
+    fn target(&self) -> *mut libc::c_void {
+        self.done as *mut libc::c_void
+    }
+
+    /// Hands the sender over to FASTER if the operation went pending, which releases it once
+    /// the operation has completed.
+    fn issued(mut self, status: u8) {
+        if Status::from(status) == Status::Pending {
+            self.done = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        if !self.done.is_null() {
+            drop(unsafe { Box::from_raw(self.done) });
+        }
+    }
+}
+
+fn decode_swap<C, V>(current: Option<&[u8]>, swapped: bool) -> Result<Swap<V>, FasterError<'static>>
+where
+    C: Codec<V>,
+{
+    let current = current.map(C::decode).transpose()?;
+    Ok(Swap { swapped, current })
+}
+
+fn conditional_lookup<V>(
+    status: u8,
+    mut state: ReadWithState<SwapReader<V>, Swap<V>>,
+) -> Result<Lookup<Swap<V>>, FasterError<'static>> {
+    if let Some(err) = take_callback_error() {
+        return Err(err);
+    }
+    let lookup = match Status::from(status).into_result()? {
+        Status::Pending => state.receiver.take().map(Lookup::Pending),
+        _ => state.result.take().map(Lookup::Found),
+    };
+    Ok(lookup.unwrap_or(Lookup::NotFound))
+}
+
+/// A FASTER session whose operations return futures that resolve once FASTER has completed
+/// them, including operations that went pending on I/O.
+///
+/// FASTER only completes the operations of a session when the session's thread asks it to, so a
+/// future whose operation went pending drives `complete_pending` whenever it is polled. It is
+/// woken as soon as another future of the session has completed its operation, and otherwise
+/// polled again after a delay that grows from 50µs up to 10ms while the operation waits for
+/// I/O. The delays are served by a background thread, so the futures work on any executor
+/// (e.g. monoio or tokio) without a runtime-specific timer. With the `tokio` feature, a tokio
+/// runtime times the futures it polls itself. Like `Session`, it is bound to the
+/// thread that started it and should be used from a thread-local task.
+pub struct AsyncSession<'a, C = Bincode> {
+    session: Session<'a, C>,
+}
+
+impl<'a, C> AsyncSession<'a, C> {
+    pub(crate) fn new(session: Session<'a, C>) -> AsyncSession<'a, C> {
+        AsyncSession { session }
+    }
+
+    /// The id of the underlying FASTER session.
+    pub fn id(&self) -> SessionId {
+        self.session.id()
+    }
+
+    /// See `Session::last_serial`.
+    pub fn last_serial(&self) -> u64 {
+        self.session.last_serial()
+    }
+
+    pub async fn upsert<K, V>(&self, key: &K, value: &V) -> Result<(), FasterError>
+    where
+        C: Encode<K> + Encode<V>,
+        K: ?Sized,
+        V: ?Sized,
+    {
+        let (completion, done) = Completion::new();
+        let status = self.session.issue_upsert(key, value, completion)?;
+        self.complete(status, done).await
+    }
+
+    /// Reads the value of `key`, or `None` if the key does not exist. A value that cannot be
+    /// decoded fails this read only, and not the other futures of the session that happen to
+    /// complete it.
+    pub async fn read<K, V>(&self, key: &K) -> Result<Option<V>, FasterError>
+    where
+        C: Encode<K> + Codec<V>,
+        K: ?Sized,
+    {
+        let (status, mut receiver) = self
+            .session
+            .issue_read::<K, Result<Option<V>, FasterError<'static>>>(key, read_option_callback::<C, V>)?;
+        let result = match status {
+            Status::Pending => self.receive(receiver).await?,
+            // The callback already ran, so the receiver is filled
+            _ => receiver.recv().await,
+        };
+        // Every completed read sends a result, even for a key that does not exist
+        result.unwrap_or(Err(FasterError::OperationError(Status::Pending)))
+    }
+
+    /// Writes `new` to `key` if its current value is equal to `expected`, see
+    /// `Session::compare_and_swap`. Returns `None` if `key` has no record.
+    pub async fn compare_and_swap<K, V>(
+        &self,
+        key: &K,
+        expected: &V,
+        new: &V,
+    ) -> Result<Option<Swap<V>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let lookup = self.session.compare_and_swap(key, expected, new)?;
+        self.resolve(lookup).await
+    }
+
+    /// Writes `value` to `key` only if `key` has no record, see `Session::insert_if_absent`.
+    /// Returns `None` only if FASTER could not complete the operation.
+    pub async fn insert_if_absent<K, V>(&self, key: &K, value: &V) -> Result<Option<Swap<V>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let lookup = self.session.insert_if_absent(key, value)?;
+        self.resolve(lookup).await
+    }
+
+    async fn resolve<R>(&self, lookup: Lookup<R>) -> Result<Option<R>, FasterError> {
+        match lookup {
+            Lookup::Found(result) => Ok(Some(result)),
+            Lookup::NotFound => Ok(None),
+            Lookup::Pending(receiver) => self.receive(receiver).await?.transpose(),
+        }
+    }
+
+    /// Waits for the result of a pending operation, driving `complete_pending` until it arrives
+    /// or the receiver is closed.
+    async fn receive<R>(&self, mut receiver: Receiver<R>) -> Result<Option<R>, FasterError> {
+        self.drive(|cx| match Pin::new(&mut receiver).poll_next(cx) {
+            Poll::Ready(value) => Some(value),
+            Poll::Pending => None,
+        })
+        .await
+    }
+
+    /// Drives `complete_pending` until `ready` returns a result, backing off between polls.
+    async fn drive<R, F>(&self, mut ready: F) -> Result<R, FasterError>
+    where
+        F: FnMut(&mut Context<'_>) -> Option<R>,
+    {
+        let mut backoff = Backoff::new();
+        poll_fn(|cx| {
+            if let Some(result) = ready(cx) {
+                return Poll::Ready(Ok(result));
+            }
+            if let Err(err) = self.session.complete_pending(false) {
+                return Poll::Ready(Err(err));
+            }
+            match ready(cx) {
+                Some(result) => Poll::Ready(Ok(result)),
+                None => {
+                    backoff.wake_later(cx.waker());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    pub async fn rmw<K, V>(&self, key: &K, modification: &V) -> Result<(), FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        V: FasterRmw,
+    {
+        let (completion, done) = Completion::new();
+        let status = self.session.issue_rmw(key, modification, completion)?;
+        self.complete(status, done).await
+    }
+
+    /// Reads the values of `keys`, see `Session::multi_get`.
+    pub async fn multi_get<K, V>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Result<Option<V>, FasterError<'static>>>, FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let mut batch = self.session.issue_multi_get(keys);
+        if batch.pending {
+            let slots = &batch.slots;
+            self.drive(|_| slots.iter().all(Option::is_some).then_some(())).await?;
+            batch.pending = false;
+        }
+        Ok(batch.into_results())
+    }
+
+    /// Upserts `entries` in order, see `Session::multi_upsert`.
+    pub async fn multi_upsert<K, V>(&self, entries: &[(K, V)]) -> Result<(), FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+    {
+        let (_, receivers) = self.session.issue_multi_upsert(entries, true)?;
+        for done in receivers {
+            self.complete(Status::Pending, Some(done)).await?;
+        }
+        Ok(())
+    }
+
+    /// Read-Modify-Write with closures, see `Session::rmw_with`.
+    pub async fn rmw_with<K, V, I, U>(&self, key: &K, initial: I, update: U) -> Result<(), FasterError>
+    where
+        C: Codec<K> + Codec<V>,
+        I: FnOnce() -> V + 'static,
+        U: Fn(&V) -> V + 'static,
+    {
+        let (sender, done) = channel();
+        let status = self.session.issue_rmw_with(key, initial, update, Some(sender))?;
+        self.complete(status, Some(done)).await
+    }
+
+    pub async fn delete<K>(&self, key: &K) -> Result<(), FasterError>
+    where
+        C: Encode<K>,
+        K: ?Sized,
+    {
+        let (completion, done) = Completion::new();
+        let status = self.session.issue_delete(key, completion)?;
+        self.complete(status, done).await
+    }
+
+    /// Waits until FASTER has completed an operation that went pending, which notifies `done`.
+    /// Other pending operations of this session are completed along the way, but not waited for.
+    async fn complete(&self, status: Status, done: Option<Receiver<Completed>>) -> Result<(), FasterError> {
+        let done = match (status, done) {
+            (Status::Pending, Some(done)) => done,
+            _ => return Ok(()),
+        };
+        match self.receive(done).await? {
+            Some(completed) => completed.map(|_| ()),
+            // The session dropped the operation without completing it
+            None => Err(FasterError::OperationError(Status::Pending)),
+        }
+    }
+}
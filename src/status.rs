@@ -1,9 +1,108 @@
 // Represents Status values from FASTER
 
+extern crate libfaster_sys as ffi;
+
+use crate::FasterError;
+use std::fmt;
+
+/// Status of an operation as reported by FASTER.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    Pending,
+    NotFound,
+    OutOfMemory,
+    IoError,
+    Corruption,
+    Aborted,
+}
+
+impl Status {
+    /// Returns `true` for the statuses that indicate a failed operation.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Status::Ok | Status::Pending | Status::NotFound)
+    }
+
+    /// Turns error statuses into a `FasterError` so they can be propagated with `?`.
+    pub(crate) fn into_result(self) -> Result<Status, FasterError<'static>> {
+        if self.is_error() {
+            Err(FasterError::OperationError(self))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+impl From<ffi::faster_status> for Status {
+    /// Codes outside of `faster_status` can only come from a corrupted result and are
+    /// reported as such.
+    fn from(status: ffi::faster_status) -> Self {
+        match status {
+            ffi::faster_status_Ok => Status::Ok,
+            ffi::faster_status_Pending => Status::Pending,
+            ffi::faster_status_NotFound => Status::NotFound,
+            ffi::faster_status_OutOfMemory => Status::OutOfMemory,
+            ffi::faster_status_IOError => Status::IoError,
+            ffi::faster_status_Corrupted => Status::Corruption,
+            ffi::faster_status_Aborted => Status::Aborted,
+            _ => Status::Corruption,
+        }
+    }
+}
+
+impl From<u8> for Status {
+    fn from(status: u8) -> Self {
+        Status::from(status as ffi::faster_status)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Ok => "Ok",
+            Status::Pending => "Pending",
+            Status::NotFound => "NotFound",
+            Status::OutOfMemory => "OutOfMemory",
+            Status::IoError => "IoError",
+            Status::Corruption => "Corruption",
+            Status::Aborted => "Aborted",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[deprecated(note = "use `Status::Ok` instead")]
 pub const OK: u8 = 0;
+#[deprecated(note = "use `Status::Pending` instead")]
 pub const PENDING: u8 = 1;
+#[deprecated(note = "use `Status::NotFound` instead")]
 pub const NOT_FOUND: u8 = 2;
+#[deprecated(note = "use `Status::OutOfMemory` instead")]
 pub const OUT_OF_MEMORY: u8 = 3;
+#[deprecated(note = "use `Status::IoError` instead")]
 pub const IO_ERROR: u8 = 4;
+#[deprecated(note = "use `Status::Corruption` instead")]
 pub const CORRUPTION: u8 = 5;
+#[deprecated(note = "use `Status::Aborted` instead")]
 pub const ABORTED: u8 = 6;
+
+#[cfg(test)]
+mod tests {
+    use super::Status;
+
+    #[test]
+    fn converts_faster_status_codes() {
+        assert_eq!(Status::from(0u8), Status::Ok);
+        assert_eq!(Status::from(1u8), Status::Pending);
+        assert_eq!(Status::from(2u8), Status::NotFound);
+        assert_eq!(Status::from(6u8), Status::Aborted);
+        assert_eq!(Status::from(42u8), Status::Corruption);
+    }
+
+    #[test]
+    fn error_statuses_become_errors() {
+        assert!(Status::NotFound.into_result().is_ok());
+        assert!(Status::IoError.into_result().is_err());
+    }
+}
@@ -1,13 +1,38 @@
 extern crate libc;
 extern crate libfaster_sys as ffi;
 
-pub struct CheckPoint {
-    pub checked: bool,
-    pub token: String,
-}
+use crate::guid::SessionId;
+use crate::status::Status;
+use crate::FasterError;
+use local_channel::mpsc::Receiver;
 
+/// Result of `FasterKv::recover`.
+#[derive(Clone, Debug)]
 pub struct Recover {
-    pub status: u8,
+    pub status: Status,
+    /// The version of the store that was recovered.
     pub version: u32,
-    pub session_ids: Vec<String>,
+    /// The sessions that were active when the checkpoint was taken, which are continued with
+    /// `FasterKv::resume_session`.
+    pub session_ids: Vec<SessionId>,
+}
+
+/// Result of `Session::read_with` and of conditional writes like `Session::compare_and_swap`.
+pub enum Lookup<R> {
+    /// The record was in memory and the reader already ran.
+    Found(R),
+    NotFound,
+    /// The record has to be fetched from disk. The reader runs when the operation is completed
+    /// by `complete_pending`, and its result is sent to the receiver, as is an error that fails
+    /// the operation. The receiver closes without a value if the key does not exist.
+    Pending(Receiver<Result<R, FasterError<'static>>>),
+}
+
+/// Outcome of `Session::compare_and_swap` and `Session::insert_if_absent`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Swap<V> {
+    /// Whether the new value was written.
+    pub swapped: bool,
+    /// The value of the record before the operation, or `None` if there was no record.
+    pub current: Option<V>,
 }
@@ -0,0 +1,77 @@
+extern crate faster_rs;
+extern crate tempfile;
+
+use faster_rs::{FasterError, FasterKvBuilder, Lookup, Status};
+use local_channel::mpsc::Receiver;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use tempfile::TempDir;
+
+// Tracks the bytes allocated from Rust that have not been freed yet. The targets that Rust hands
+// to FASTER for pending operations (senders and closure state) show up as a difference if they
+// are never freed. The copies that the C++ wrapper makes of keys and values are allocated with
+// malloc, which this allocator does not see; those are checked by the AddressSanitizer build.
+struct TrackingAllocator;
+
+static LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size() as isize, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size() as isize, Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+// The only test in this file, so that no other test allocates concurrently
+#[test]
+fn pending_operations_release_their_buffers() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    // Write more than fits into the in-memory log so that operations on the first keys go pending
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).unwrap();
+    }
+    session.complete_pending(true).unwrap();
+
+    let live_before = LIVE_BYTES.load(Ordering::SeqCst);
+    let (mut reads, mut rmws, mut swaps) = (0, 0, 0);
+    for key in 0..64u64 {
+        let (status, receiver): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(&key).unwrap();
+        if status == Status::Pending {
+            reads += 1;
+        }
+        drop(receiver);
+        let status = session
+            .rmw_with(&(key + 64), Vec::new, |current: &Vec<u8>| current[..1].to_vec())
+            .unwrap();
+        if status == Status::Pending {
+            rmws += 1;
+        }
+        if let Lookup::Pending(receiver) = session.compare_and_swap(&(key + 128), &value, &vec![8u8]).unwrap() {
+            swaps += 1;
+            drop(receiver);
+        }
+    }
+    session.complete_pending(true).unwrap();
+    assert!(reads > 0, "Reads should have gone pending");
+    assert!(rmws > 0, "RMWs should have gone pending");
+    assert!(swaps > 0, "Conditional writes should have gone pending");
+    assert_eq!(LIVE_BYTES.load(Ordering::SeqCst), live_before);
+}
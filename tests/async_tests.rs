@@ -0,0 +1,233 @@
+extern crate faster_rs;
+extern crate tempfile;
+
+use faster_rs::{FasterError, FasterKv, FasterKvBuilder, Swap};
+use tempfile::TempDir;
+
+#[monoio::test]
+async fn async_upsert_then_read() {
+    let store = FasterKv::default();
+    let session = store.async_session();
+    let key: u64 = 1;
+    let value: u64 = 1337;
+
+    session.upsert(&key, &value).await.unwrap();
+    let read: Option<u64> = session.read(&key).await.unwrap();
+    assert_eq!(read, Some(value));
+}
+
+#[monoio::test]
+async fn async_read_missing_key() {
+    let store = FasterKv::default();
+    let session = store.async_session();
+
+    let read: Option<u64> = session.read(&(1u64)).await.unwrap();
+    assert_eq!(read, None);
+}
+
+#[monoio::test]
+async fn async_rmw_and_delete() {
+    let store = FasterKv::default();
+    let session = store.async_session();
+    let key: u64 = 1;
+
+    session.upsert(&key, &(5u64)).await.unwrap();
+    session.rmw(&key, &(10u64)).await.unwrap();
+    let read: Option<u64> = session.read(&key).await.unwrap();
+    assert_eq!(read, Some(15));
+
+    session.delete(&key).await.unwrap();
+    let read: Option<u64> = session.read(&key).await.unwrap();
+    assert_eq!(read, None);
+}
+
+#[tokio::test]
+async fn async_session_on_tokio() {
+    let store = FasterKv::default();
+    let session = store.async_session();
+    let key = String::from("key");
+    let value = String::from("value");
+
+    session.upsert(&key, &value).await.unwrap();
+    let read: Option<String> = session.read(&key).await.unwrap();
+    assert_eq!(read, Some(value));
+}
+
+#[tokio::test]
+async fn async_read_completes_pending_io() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+    for key in 0..16 {
+        let read: Option<Vec<u8>> = session.read(&key).await.unwrap();
+        assert_eq!(read, Some(value.clone()));
+    }
+}
+
+#[tokio::test]
+async fn async_compare_and_swap_completes_pending_io() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let swapped = vec![8u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+    for key in 0..16 {
+        let swap = session.compare_and_swap(&key, &value, &swapped).await.unwrap();
+        assert_eq!(swap, Some(Swap { swapped: true, current: Some(value.clone()) }));
+        let swap = session.insert_if_absent(&key, &value).await.unwrap();
+        assert_eq!(swap, Some(Swap { swapped: false, current: Some(swapped.clone()) }));
+    }
+    let swap = session.compare_and_swap(&num_keys, &value, &swapped).await.unwrap();
+    assert_eq!(swap, None);
+}
+
+#[tokio::test]
+async fn async_rmw_with_completes_pending_io() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+    for key in 0..16 {
+        session
+            .rmw_with(&key, Vec::new, |current: &Vec<u8>| current[..1].to_vec())
+            .await
+            .unwrap();
+        let read: Option<Vec<u8>> = session.read(&key).await.unwrap();
+        assert_eq!(read, Some(vec![7u8]));
+    }
+}
+
+#[tokio::test]
+async fn async_multi_get_completes_pending_io() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for start in (0..num_keys).step_by(1024) {
+        let batch: Vec<(u64, Vec<u8>)> = (start..start + 1024).map(|key| (key, value.clone())).collect();
+        session.multi_upsert(&batch).await.unwrap();
+    }
+
+    // Mixes keys on disk with keys in memory and a missing key
+    let keys: Vec<u64> = (0..16).chain(num_keys - 16..num_keys + 1).collect();
+    let values = session.multi_get::<u64, Vec<u8>>(&keys).await.unwrap();
+    let (missing, found) = values.split_last().unwrap();
+    assert!(found.iter().all(|read| read.as_ref().unwrap() == &Some(value.clone())));
+    assert_eq!(missing.as_ref().unwrap(), &None);
+}
+
+#[tokio::test]
+async fn async_operations_wait_for_their_own_completion() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+
+    // Operations on the same session that go pending at the same time all complete
+    let modification = vec![8u8];
+    let (first, second, rmw, delete) = tokio::join!(
+        session.read::<u64, Vec<u8>>(&0),
+        session.read::<u64, Vec<u8>>(&1),
+        session.rmw(&2u64, &modification),
+        session.delete(&3u64),
+    );
+    assert_eq!(first.unwrap(), Some(value.clone()));
+    assert_eq!(second.unwrap(), Some(value.clone()));
+    rmw.unwrap();
+    delete.unwrap();
+
+    let mut appended = value.clone();
+    appended.push(8);
+    assert_eq!(session.read::<u64, Vec<u8>>(&2).await.unwrap(), Some(appended));
+    assert_eq!(session.read::<u64, Vec<u8>>(&3).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn async_errors_go_to_the_operation_that_raised_them() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Values that do not decode as u64, followed by more than fits into the in-memory log
+    session.upsert(&0u64, &42u8).await.unwrap();
+    session.upsert(&1u64, &42u8).await.unwrap();
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 2..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+
+    // Whichever future completes the pending operations, each gets its own outcome
+    let (corrupt, rmw, valid) = tokio::join!(
+        session.read::<u64, u64>(&0),
+        session.rmw(&1u64, &1u64),
+        session.read::<u64, Vec<u8>>(&2),
+    );
+    match corrupt {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+    match rmw {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+    assert_eq!(valid.unwrap(), Some(value));
+}
@@ -1,20 +1,53 @@
 extern crate faster_rs;
 
-use faster_rs::{status, FasterKv};
+use faster_rs::{FasterError, FasterKv, FasterKvBuilder, FasterRmw, Lookup, Status, Swap};
+use serde::ser::{Error, Serializer};
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashSet;
 use local_channel::mpsc::Receiver;
+use tempfile::TempDir;
+
+// Counts the modifications of a key along with their sum
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Tally {
+    count: u64,
+    sum: u64,
+}
+
+impl FasterRmw for Tally {
+    fn rmw(&self, modification: Self) -> Self {
+        Tally {
+            count: self.count + 1,
+            sum: self.sum + modification.sum,
+        }
+    }
+
+    fn initial(modification: Self) -> Self {
+        Tally {
+            count: 1,
+            sum: modification.sum,
+        }
+    }
+
+    fn rmw_in_place(&mut self, modification: &Self) -> bool {
+        self.count += 1;
+        self.sum += modification.sum;
+        true
+    }
+}
 
 #[monoio::test]
 async fn faster_check() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value: u64 = 1337;
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert_eq!((upsert == status::OK || upsert == status::PENDING), true);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert_eq!((upsert == Status::Ok || upsert == Status::Pending), true);
 
-    let rmw = store.rmw(&key, &(5u64), 1);
-    assert_eq!(rmw, status::OK);
+    let rmw = session.rmw(&key, &(5u64)).unwrap();
+    assert_eq!(rmw, Status::Ok);
 
     assert!(store.size() > 0);
 }
@@ -22,145 +55,186 @@ async fn faster_check() {
 #[monoio::test]
 async fn faster_read_inserted_value() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value: u64 = 1337;
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert_eq!((upsert == status::OK || upsert == status::PENDING), true);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert_eq!((upsert == Status::Ok || upsert == Status::Pending), true);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
 }
 
 #[monoio::test]
 async fn faster_read_missing_value_recv_error() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::NOT_FOUND);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::NotFound);
     assert!(recv.recv().await.is_none());
 }
 
 #[monoio::test]
 async fn faster_rmw_changes_values() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value: u64 = 1337;
     let modification: u64 = 100;
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert_eq!((upsert == status::OK || upsert == status::PENDING), true);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert_eq!((upsert == Status::Ok || upsert == Status::Pending), true);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
 
-    let rmw = store.rmw(&key, &modification, 1);
-    assert_eq!((rmw == status::OK || rmw == status::PENDING), true);
+    let rmw = session.rmw(&key, &modification).unwrap();
+    assert_eq!((rmw == Status::Ok || rmw == Status::Pending), true);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value + modification);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value + modification);
 }
 
 #[monoio::test]
 async fn faster_rmw_without_upsert() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let modification: u64 = 100;
 
-    let rmw = store.rmw(&key, &modification, 1);
-    assert_eq!((rmw == status::OK || rmw == status::PENDING), true);
+    let rmw = session.rmw(&key, &modification).unwrap();
+    assert_eq!((rmw == Status::Ok || rmw == Status::Pending), true);
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), modification);
+}
+
+#[monoio::test]
+async fn faster_rmw_wraps_around_on_overflow() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    session.upsert(&key, &(u64::MAX - 1)).unwrap();
+    session.rmw(&key, &(3u64)).unwrap();
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), 1);
+}
+
+#[monoio::test]
+async fn faster_rmw_updates_numbers_in_place() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    session.upsert(&key, &(0u64)).unwrap();
+    // The record is in the mutable region, so no update appends to the log
+    let tail = store.tail_address();
+    for _ in 0..1000 {
+        session.rmw(&key, &(1u64)).unwrap();
+    }
+    assert_eq!(store.tail_address(), tail);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), modification);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), 1000);
 }
 
 #[monoio::test]
 async fn faster_rmw_string() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value = String::from("Hello, ");
     let modification = String::from("World!");
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert!(upsert == status::OK || upsert == status::PENDING);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert!(upsert == Status::Ok || upsert == Status::Pending);
 
-    let (res, mut recv): (u8, Receiver<String>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value);
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
 
-    let rmw = store.rmw(&key, &modification, 1);
-    assert!(rmw == status::OK || rmw == status::PENDING);
+    let rmw = session.rmw(&key, &modification).unwrap();
+    assert!(rmw == Status::Ok || rmw == Status::Pending);
 
-    let (res, mut recv): (u8, Receiver<String>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), String::from("Hello, World!"));
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), String::from("Hello, World!"));
 }
 
 #[monoio::test]
 async fn faster_rmw_vec() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value = vec![0, 1, 2];
     let modification = vec![3, 4, 5];
     let modification2 = vec![6, 7, 8, 9, 10];
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert!(upsert == status::OK || upsert == status::PENDING);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert!(upsert == Status::Ok || upsert == Status::Pending);
 
-    let (res, mut recv): (u8, Receiver<Vec<i32>>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value);
+    let (res, mut recv): (Status, Receiver<Result<Vec<i32>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
 
-    let rmw = store.rmw(&key, &modification, 1);
-    assert!(rmw == status::OK || rmw == status::PENDING);
+    let rmw = session.rmw(&key, &modification).unwrap();
+    assert!(rmw == Status::Ok || rmw == Status::Pending);
 
-    let (res, mut recv): (u8, Receiver<Vec<i32>>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), vec![0, 1, 2, 3, 4, 5]);
+    let (res, mut recv): (Status, Receiver<Result<Vec<i32>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), vec![0, 1, 2, 3, 4, 5]);
 
-    let rmw = store.rmw(&key, &modification2, 1);
-    assert!(rmw == status::OK || rmw == status::PENDING);
+    let rmw = session.rmw(&key, &modification2).unwrap();
+    assert!(rmw == Status::Ok || rmw == Status::Pending);
 
-    let (res, mut recv): (u8, Receiver<Vec<i32>>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    let (res, mut recv): (Status, Receiver<Result<Vec<i32>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
 }
 
 #[monoio::test]
 async fn faster_rmw_grow_string() {
     let store = FasterKv::default();
+    let session = store.session();
     let key = String::from("growing_string");
     let final_string = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
     for i in 0..final_string.len() {
         let letter: String = final_string.get(i..i + 1).unwrap().to_string();
-        store.rmw(&key, &letter, 1);
+        session.rmw(&key, &letter).unwrap();
     }
 
-    let (res, mut recv): (u8, Receiver<String>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), final_string);
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), final_string);
 }
 
 #[monoio::test]
 async fn faster_rmw_hashset() {
     let store = FasterKv::default();
+    let session = store.session();
     let key = String::from("set");
     {
         let a: HashSet<i32> = [1, 2, 3].iter().cloned().collect();
-        store.rmw(&key, &a, 1);
+        session.rmw(&key, &a).unwrap();
     }
     {
         let b: HashSet<i32> = [4, 2, 3, 4, 5].iter().cloned().collect();
-        store.rmw(&key, &b, 1);
+        session.rmw(&key, &b).unwrap();
     }
-    let (res, mut recv): (u8, Receiver<HashSet<i32>>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    let hash_set = recv.recv().await.unwrap();
+    let (res, mut recv): (Status, Receiver<Result<HashSet<i32>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    let hash_set = recv.recv().await.unwrap().unwrap();
     assert_eq!(hash_set.len(), 5);
     assert!(hash_set.contains(&1));
     assert!(hash_set.contains(&2));
@@ -172,20 +246,464 @@ async fn faster_rmw_hashset() {
 #[monoio::test]
 async fn faster_delete_inserted_value() {
     let store = FasterKv::default();
+    let session = store.session();
     let key: u64 = 1;
     let value: u64 = 1337;
 
-    let upsert = store.upsert(&key, &value, 1);
-    assert_eq!((upsert == status::OK || upsert == status::PENDING), true);
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert_eq!((upsert == Status::Ok || upsert == Status::Pending), true);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::OK);
-    assert_eq!(recv.recv().await.unwrap(), value);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
 
-    let delete = store.delete(&key, 1);
-    assert_eq!((delete == status::OK || delete == status::PENDING), true);
+    let delete = session.delete(&key).unwrap();
+    assert_eq!((delete == Status::Ok || delete == Status::Pending), true);
 
-    let (res, mut recv): (u8, Receiver<u64>) = store.read(&key, 1);
-    assert_eq!(res, status::NOT_FOUND);
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::NotFound);
     assert!(recv.recv().await.is_none());
 }
+
+#[monoio::test]
+async fn faster_read_with_wrong_type_errors() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    let value: u8 = 42;
+
+    session.upsert(&key, &value).unwrap();
+
+    let (res, mut recv) = session.read::<u64, u64>(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    match recv.recv().await {
+        Some(Err(FasterError::Serialization(_))) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+}
+
+#[monoio::test]
+async fn faster_pending_read_errors_go_to_their_receiver() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 15, 1 << 27);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    let address = store.tail_address();
+    session.upsert(&(0u64), &(42u8)).unwrap();
+    session.upsert(&(1u64), &(7u64)).unwrap();
+    // Pushes both records out of memory
+    let filler = vec![0u8; 1024];
+    let mut filler_key = 2u64;
+    while store.head_address() <= address + 64 {
+        session.upsert(&filler_key, &filler).unwrap();
+        filler_key += 1;
+    }
+
+    let (res, mut corrupt) = session.read::<u64, u64>(&(0u64)).unwrap();
+    assert_eq!(res, Status::Pending);
+    let (res, mut valid) = session.read::<u64, u64>(&(1u64)).unwrap();
+    assert_eq!(res, Status::Pending);
+
+    // Completing the reads does not report the error of one of them
+    session.complete_pending(true).unwrap();
+    match corrupt.recv().await {
+        Some(Err(FasterError::Serialization(_))) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+    assert_eq!(valid.recv().await.unwrap().unwrap(), 7);
+}
+
+#[monoio::test]
+async fn faster_rmw_computes_initial_value() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    for sum in 1..=3 {
+        session.rmw(&key, &Tally { count: 0, sum }).unwrap();
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<Tally, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), Tally { count: 3, sum: 6 });
+}
+
+#[monoio::test]
+async fn faster_rmw_grows_value_out_of_place() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    // The string outgrows its record, so it cannot be appended to in place
+    session.rmw(&key, &String::from("Hello")).unwrap();
+    for _ in 0..100 {
+        session.rmw(&key, &String::from(", World")).unwrap();
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap().len(), 5 + 100 * 7);
+}
+
+#[monoio::test]
+async fn faster_rmw_with_wrong_type_keeps_value() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    let value: u8 = 42;
+
+    session.upsert(&key, &value).unwrap();
+
+    match session.rmw(&key, &(5u64)) {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<u8, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+}
+
+#[monoio::test]
+async fn faster_rmw_with_closures() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    // The initial value is computed for a missing key, the modification is never stored as is
+    let rmw = session.rmw_with(&key, || 10u64, |current: &u64| current * 2).unwrap();
+    assert_eq!(rmw, Status::Ok);
+    // Multiplies instead of adding like `rmw` does for u64
+    session.rmw_with(&key, || 1u64, |current: &u64| current * 3).unwrap();
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), 30);
+}
+
+#[monoio::test]
+async fn faster_rmw_with_closures_wrong_type_keeps_value() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    let value: u8 = 42;
+
+    session.upsert(&key, &value).unwrap();
+
+    match session.rmw_with(&key, || 0u64, |current: &u64| current + 1) {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<u8, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+}
+
+// A value that Bincode fails to encode once it is larger than its limit
+#[derive(Deserialize, Debug, PartialEq)]
+struct Limited(u64);
+
+impl serde::Serialize for Limited {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            0..=100 => serializer.serialize_u64(self.0),
+            _ => Err(S::Error::custom("Value exceeds its limit")),
+        }
+    }
+}
+
+#[monoio::test]
+async fn faster_rmw_with_failed_initial_creates_no_record() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    match session.rmw_with(&key, || Limited(1000), |current: &Limited| Limited(current.0 + 1)) {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<Limited, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::NotFound);
+    assert!(recv.recv().await.is_none());
+
+    // The key is created once its initial value can be encoded
+    session.rmw_with(&key, || Limited(10), |current: &Limited| Limited(current.0 + 1)).unwrap();
+    session.rmw_with(&key, || Limited(10), |current: &Limited| Limited(current.0 + 1)).unwrap();
+    let (res, mut recv): (Status, Receiver<Result<Limited, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), Limited(11));
+}
+
+// Starts a key at a hundred times its first modification
+impl FasterRmw for Limited {
+    fn rmw(&self, modification: Self) -> Self {
+        Limited(self.0 + modification.0)
+    }
+
+    fn initial(modification: Self) -> Self {
+        Limited(modification.0 * 100)
+    }
+}
+
+#[monoio::test]
+async fn faster_rmw_failed_initial_creates_no_record() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    match session.rmw(&key, &Limited(5)) {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<Limited, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::NotFound);
+    assert!(recv.recv().await.is_none());
+
+    session.rmw(&key, &Limited(1)).unwrap();
+    let (res, mut recv): (Status, Receiver<Result<Limited, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), Limited(100));
+}
+
+#[monoio::test]
+async fn faster_read_with_lends_record_bytes() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    let value = String::from("Hello, World!");
+
+    session.upsert(&key, &value).unwrap();
+
+    match session.read_with(&key, |bytes: &[u8]| bytes.to_vec()).unwrap() {
+        Lookup::Found(bytes) => assert_eq!(bytes, bincode::serialize(&value).unwrap()),
+        _ => panic!("Record should be in memory"),
+    }
+
+    match session.read_with(&2u64, |bytes: &[u8]| bytes.len()).unwrap() {
+        Lookup::NotFound => {}
+        _ => panic!("Key should not be found"),
+    }
+}
+
+#[monoio::test]
+async fn faster_compare_and_swap() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    let version: u64 = 7;
+
+    session.upsert(&key, &version).unwrap();
+
+    match session.compare_and_swap(&key, &version, &(version + 1)).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: true, current: Some(version) }),
+        _ => panic!("Record should be in memory"),
+    }
+    // A stale expected value leaves the record as it is
+    match session.compare_and_swap(&key, &version, &(version + 2)).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: false, current: Some(version + 1) }),
+        _ => panic!("Record should be in memory"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), version + 1);
+}
+
+#[monoio::test]
+async fn faster_compare_and_swap_missing_key_writes_nothing() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+
+    match session.compare_and_swap(&key, &(1u64), &(2u64)).unwrap() {
+        Lookup::NotFound => {}
+        _ => panic!("Key should not be found"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::NotFound);
+    assert!(recv.recv().await.is_none());
+}
+
+#[monoio::test]
+async fn faster_failed_conditional_write_copies_read_only_record() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 15, 1 << 27);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+    let key: u64 = 1;
+    let version: u64 = 7;
+
+    let address = store.tail_address();
+    session.upsert(&key, &version).unwrap();
+    // Pushes the record out of the mutable region, but keeps it in memory
+    let filler = vec![0u8; 1024];
+    let mut filler_key = key + 1;
+    while store.read_only_address() <= address {
+        session.upsert(&filler_key, &filler).unwrap();
+        filler_key += 1;
+    }
+    assert!(store.head_address() <= address, "Record should be in memory");
+
+    // The RMW cannot be aborted, so the record is copied to the tail as it is
+    let tail = store.tail_address();
+    match session.compare_and_swap(&key, &(version + 1), &(version + 2)).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: false, current: Some(version) }),
+        _ => panic!("Record should be in memory"),
+    }
+    assert!(store.tail_address() > tail);
+
+    // The copy is in the mutable region, where a failed condition writes nothing
+    let tail = store.tail_address();
+    match session.compare_and_swap(&key, &(version + 1), &(version + 2)).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: false, current: Some(version) }),
+        _ => panic!("Record should be in memory"),
+    }
+    match session.insert_if_absent(&key, &(version + 3)).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: false, current: Some(version) }),
+        _ => panic!("Record should be in memory"),
+    }
+    assert_eq!(store.tail_address(), tail);
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), version);
+}
+
+#[monoio::test]
+async fn faster_insert_if_absent() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key = String::from("order");
+
+    match session.insert_if_absent(&key, &String::from("first")).unwrap() {
+        Lookup::Found(swap) => assert_eq!(swap, Swap { swapped: true, current: None }),
+        _ => panic!("Record should be inserted in memory"),
+    }
+    match session.insert_if_absent(&key, &String::from("second")).unwrap() {
+        Lookup::Found(swap) => {
+            assert_eq!(swap, Swap { swapped: false, current: Some(String::from("first")) })
+        }
+        _ => panic!("Record should be in memory"),
+    }
+
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), "first");
+}
+
+#[test]
+fn faster_multi_get_reads_own_multi_upsert() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let entries: Vec<(u64, String)> = (0..100).map(|key| (key, key.to_string())).collect();
+
+    session.multi_upsert(&entries).unwrap();
+
+    let keys: Vec<u64> = (0..110).collect();
+    let values = session.multi_get::<u64, String>(&keys).unwrap();
+    assert_eq!(values.len(), keys.len());
+    for (key, value) in keys.iter().zip(values) {
+        match *key < 100 {
+            true => assert_eq!(value.unwrap(), Some(key.to_string())),
+            false => assert_eq!(value.unwrap(), None),
+        }
+    }
+}
+
+#[test]
+fn faster_multi_get_fails_reads_individually() {
+    let store = FasterKv::default();
+    let session = store.session();
+
+    session.upsert(&(1u64), &(42u8)).unwrap();
+    session.upsert(&(2u64), &(42u64)).unwrap();
+
+    let values = session.multi_get::<u64, u64>(&[1, 2]).unwrap();
+    match &values[0] {
+        Err(FasterError::Serialization(_)) => {}
+        _ => panic!("Should give Serialization error"),
+    }
+    assert_eq!(values[1].as_ref().unwrap(), &Some(42));
+}
+
+#[test]
+fn faster_session_restarts_after_drop() {
+    let store = FasterKv::default();
+    let key: u64 = 1;
+    {
+        let session = store.session();
+        session.upsert(&key, &(1337u64)).unwrap();
+    }
+
+    let session = store.session();
+    let (res, _recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+}
+
+#[test]
+#[should_panic(expected = "already active")]
+fn faster_second_session_on_thread_panics() {
+    let store = FasterKv::default();
+    let _session = store.session();
+    let _second = store.session();
+}
+
+#[monoio::test]
+async fn faster_sessions_of_different_stores_share_thread() {
+    let first = FasterKv::default();
+    let second = FasterKv::default();
+    let first_session = first.session();
+    let second_session = second.session();
+    first_session.upsert(&(1u64), &(10u64)).unwrap();
+    second_session.upsert(&(1u64), &(20u64)).unwrap();
+
+    // Scans and compaction run within the session of their own store
+    let items: Vec<(u64, u64)> = second.iter().map(Result::unwrap).collect();
+    assert_eq!(items, vec![(1, 20)]);
+    let until = first.tail_address();
+    first_session.upsert(&(2u64), &(30u64)).unwrap();
+    assert!(first.compact(until));
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = first_session.read(&(1u64)).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.transpose().unwrap(), Some(10));
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = second_session.read(&(1u64)).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.transpose().unwrap(), Some(20));
+}
+
+#[test]
+fn faster_session_numbers_operations() {
+    let store = FasterKv::default();
+    let session = store.session();
+    let key: u64 = 1;
+    assert_eq!(session.last_serial(), 0);
+
+    session.upsert(&key, &(1337u64)).unwrap();
+    session.rmw(&key, &(5u64)).unwrap();
+    let _ = session.read::<u64, u64>(&key).unwrap();
+    session.delete(&key).unwrap();
+    assert_eq!(session.last_serial(), 4);
+
+    session.set_serial(100);
+    session.upsert(&key, &(1337u64)).unwrap();
+    assert_eq!(session.last_serial(), 101);
+}
+
+#[test]
+#[should_panic(expected = "monotonic")]
+fn faster_session_rejects_decreasing_serial() {
+    let store = FasterKv::default();
+    let session = store.session();
+    session.set_serial(10);
+    session.set_serial(9);
+}
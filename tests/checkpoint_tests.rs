@@ -1,7 +1,14 @@
 extern crate faster_rs;
 extern crate tempfile;
 
-use faster_rs::{FasterError, FasterKv, FasterKvBuilder};
+use faster_rs::{
+    CheckpointKind, CheckpointMode, CheckpointScheduler, CheckpointToken, FasterError, FasterKv,
+    FasterKvBuilder, Recover, RetentionPolicy, SessionId, Status,
+};
+use local_channel::mpsc::Receiver;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use tempfile::TempDir;
 
 #[test]
@@ -13,15 +20,16 @@ fn single_checkpoint() {
     let mut builder = FasterKvBuilder::new(table_size, log_size);
     builder.with_disk(&dir_path);
     let store = builder.build().unwrap();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint().unwrap();
     assert_eq!(checkpoint.checked, true);
-    assert_eq!(checkpoint.token.len(), 37 - 1); // -1 \0
+    assert_eq!(checkpoint.token.unwrap().to_string().len(), 36);
 }
 
 #[test]
@@ -33,15 +41,16 @@ fn single_checkpoint_index() {
     let mut builder = FasterKvBuilder::new(table_size, log_size);
     builder.with_disk(&dir_path);
     let store = builder.build().unwrap();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint_index().unwrap();
     assert_eq!(checkpoint.checked, true);
-    assert_eq!(checkpoint.token.len(), 37 - 1); // -1 \0
+    assert_eq!(checkpoint.token.unwrap().to_string().len(), 36);
 }
 
 #[test]
@@ -53,15 +62,280 @@ fn single_checkpoint_hybrid_log() {
     let mut builder = FasterKvBuilder::new(table_size, log_size);
     builder.with_disk(&dir_path);
     let store = builder.build().unwrap();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint_hybrid_log().unwrap();
     assert_eq!(checkpoint.checked, true);
-    assert_eq!(checkpoint.token.len(), 37 - 1); // -1 \0
+    assert_eq!(checkpoint.token.unwrap().to_string().len(), 36);
+}
+
+#[test]
+fn checkpoint_wait_reports_persistent_serial() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1073741824;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+    let value: u64 = 100;
+
+    for key in 0..1000 {
+        session.upsert(&(key as u64), &value).unwrap();
+    }
+
+    let checkpoint = store.checkpoint().unwrap();
+    assert!(checkpoint.checked);
+    let persisted = checkpoint.wait(&session).unwrap();
+    assert!(checkpoint.is_persisted());
+    assert_eq!(Some(persisted.token), checkpoint.token);
+    assert_eq!(persisted.sessions.len(), 1);
+    assert_eq!(persisted.sessions[0].session_id, session.id());
+    assert_eq!(persisted.sessions[0].persistent_serial, session.last_serial());
+}
+
+#[test]
+fn checkpoint_reports_session_of_its_own_store() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1073741824;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let other_dir = TempDir::new().unwrap();
+    let other_path = other_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&other_path);
+    let other = builder.build().unwrap();
+
+    // The other store's session runs on the same thread
+    let other_session = other.session();
+    let session = store.session();
+    for key in 0..1000u64 {
+        session.upsert(&key, &key).unwrap();
+        other_session.upsert(&key, &key).unwrap();
+    }
+
+    let checkpoint = store.checkpoint().unwrap();
+    assert!(checkpoint.checked);
+    let persisted = checkpoint.wait(&session).unwrap();
+    assert_eq!(persisted.sessions.len(), 1);
+    assert_eq!(persisted.sessions[0].session_id, session.id());
+    assert_eq!(persisted.sessions[0].persistent_serial, session.last_serial());
+}
+
+#[monoio::test]
+async fn recover_latest_checkpoint() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1073741824;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let (token, session_id) = {
+        let mut builder = FasterKvBuilder::new(table_size, log_size);
+        builder.with_disk(&dir_path);
+        let store = builder.build().unwrap();
+        let session = store.session();
+        for key in 0..1000u64 {
+            session.upsert(&key, &key).unwrap();
+        }
+        let first = store.checkpoint().unwrap();
+        first.wait(&session).unwrap();
+        session.upsert(&(0u64), &(1337u64)).unwrap();
+        let second = store.checkpoint().unwrap();
+        second.wait(&session).unwrap();
+        (second.token.unwrap(), session.id())
+    };
+
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let checkpoints = store.list_checkpoints().unwrap();
+    assert_eq!(checkpoints.len(), 4);
+    let latest: Vec<_> = checkpoints.iter().filter(|c| c.token == token).collect();
+    assert_eq!(latest.len(), 2);
+    assert!(latest.iter().any(|c| c.kind == CheckpointKind::Index));
+    assert!(latest.iter().any(|c| c.kind == CheckpointKind::HybridLog));
+
+    let recovered = store.recover_latest().unwrap();
+    assert_eq!(recovered.status, Status::Ok);
+    assert_eq!(recovered.session_ids.len(), 1);
+    assert_eq!(recovered.session_ids[0], session_id);
+    let (session, last_serial) = store.resume_session(recovered.session_ids[0]);
+    assert_eq!(last_serial, 1001);
+    assert_eq!(session.last_serial(), 1001);
+    let (_, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&(0u64)).unwrap();
+    session.complete_pending(true).unwrap();
+    assert_eq!(recv.recv().await.unwrap().unwrap(), 1337);
+}
+
+#[test]
+fn recover_latest_without_checkpoints_errors() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+
+    assert!(store.list_checkpoints().unwrap().is_empty());
+    match store.recover_latest() {
+        Err(FasterError::RecoveryError) => {}
+        _ => panic!("Should give RecoveryError"),
+    }
+}
+
+#[test]
+fn retention_prunes_old_checkpoints() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder
+        .with_disk(&dir_path)
+        .with_checkpoint_retention(RetentionPolicy::keep_last(1));
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    let mut tokens = Vec::new();
+    for round in 0..3u64 {
+        session.upsert(&(0u64), &round).unwrap();
+        let checkpoint = store.checkpoint().unwrap();
+        checkpoint.wait(&session).unwrap();
+        tokens.push(checkpoint.token.unwrap());
+    }
+
+    // Pruned when the third checkpoint started, which the newest one was kept for
+    let checkpoints = store.list_checkpoints().unwrap();
+    assert!(checkpoints.iter().all(|c| c.token != tokens[0]));
+    assert_eq!(checkpoints.iter().filter(|c| c.token == tokens[2]).count(), 2);
+
+    let pruned = store.prune_checkpoints().unwrap();
+    assert_eq!(pruned.len(), 2);
+    assert!(pruned.iter().all(|c| c.token == tokens[1]));
+    assert_eq!(store.list_checkpoints().unwrap().len(), 2);
+}
+
+#[test]
+fn delete_checkpoint_keeps_latest_recoverable() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+    session.upsert(&(1u64), &(1u64)).unwrap();
+
+    let first = store.checkpoint().unwrap();
+    first.wait(&session).unwrap();
+    let first = first.token.unwrap();
+    let second = store.checkpoint().unwrap();
+    second.wait(&session).unwrap();
+    let second = second.token.unwrap();
+
+    match store.delete_checkpoint(second) {
+        Err(FasterError::CheckpointInUse) => {}
+        _ => panic!("Should give CheckpointInUse"),
+    }
+    store.delete_checkpoint(first).unwrap();
+    let checkpoints = store.list_checkpoints().unwrap();
+    assert_eq!(checkpoints.len(), 2);
+    assert!(checkpoints.iter().all(|c| c.token == second));
+    assert!(store.delete_checkpoint(first).is_err());
+}
+
+#[test]
+fn malformed_token_errors() {
+    fn recover(store: &FasterKv, token: &str) -> Result<Recover, FasterError<'static>> {
+        let token: CheckpointToken = token.parse()?;
+        Ok(store.recover(token, token).unwrap())
+    }
+
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+
+    for token in ["", "../index-checkpoints", "67e55044-10b1-426f-9247-bb680e5fe0c\0"].iter() {
+        match recover(&store, token) {
+            Err(FasterError::InvalidToken) => {}
+            _ => panic!("Should give InvalidToken for {}", token),
+        }
+    }
+    assert!("67e55044-10b1-426f-9247-bb680e5fe0c8".parse::<SessionId>().is_ok());
+}
+
+#[test]
+fn scheduler_checkpoints_after_operations() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let (sender, receiver) = mpsc::channel();
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder
+        .with_disk(&dir_path)
+        .with_checkpoint_scheduler(CheckpointScheduler::after_operations(1000).report_to(sender));
+    let store = builder.build().unwrap();
+    let session = store.session();
+    for key in 0..1000u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+
+    // The checkpoint is persisted once the session has refreshed
+    let persisted = loop {
+        session.refresh();
+        session.complete_pending(false).unwrap();
+        if let Ok(result) = receiver.recv_timeout(Duration::from_millis(10)) {
+            break result.unwrap();
+        }
+    };
+    assert_eq!(persisted.sessions.len(), 1);
+    assert_eq!(persisted.sessions[0].session_id, session.id());
+    let checkpoints = store.list_checkpoints().unwrap();
+    assert_eq!(checkpoints.iter().filter(|c| c.token == persisted.token).count(), 2);
+}
+
+#[test]
+fn scheduler_takes_hybrid_log_checkpoints_at_interval() {
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let reported = Arc::new(AtomicUsize::new(0));
+    let scheduler = {
+        let reported = Arc::clone(&reported);
+        CheckpointScheduler::every(Duration::from_millis(10))
+            .with_mode(CheckpointMode::HybridLog)
+            .on_checkpoint(move |result| {
+                result.unwrap();
+                reported.fetch_add(1, Ordering::SeqCst);
+            })
+    };
+    let mut builder = FasterKvBuilder::new(1 << 14, 1073741824);
+    builder.with_disk(&dir_path).with_checkpoint_scheduler(scheduler);
+    let store = builder.build().unwrap();
+    {
+        let session = store.session();
+        for key in 0..100u64 {
+            session.upsert(&key, &key).unwrap();
+        }
+        while reported.load(Ordering::SeqCst) < 2 {
+            session.refresh();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let checkpoints = store.list_checkpoints().unwrap();
+    assert!(checkpoints.len() >= 2);
+    assert!(checkpoints.iter().all(|c| c.kind == CheckpointKind::HybridLog));
+
+    // Dropping the store stops the scheduler
+    drop(store);
+    let stopped_at = reported.load(Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(reported.load(Ordering::SeqCst), stopped_at);
 }
 
 #[test]
@@ -72,10 +346,11 @@ fn concurrent_checkpoints() {
 #[test]
 fn in_memory_checkpoint_errors() {
     let store = FasterKv::default();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint();
@@ -89,10 +364,11 @@ fn in_memory_checkpoint_errors() {
 #[test]
 fn in_memory_checkpoint_index_errors() {
     let store = FasterKv::default();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint_index();
@@ -106,10 +382,11 @@ fn in_memory_checkpoint_index_errors() {
 #[test]
 fn in_memory_checkpoint_hybrid_log_errors() {
     let store = FasterKv::default();
+    let session = store.session();
     let value: u64 = 100;
 
     for key in 0..1000 {
-        store.upsert(&(key as u64), &value, key);
+        session.upsert(&(key as u64), &value).unwrap();
     }
 
     let checkpoint = store.checkpoint_hybrid_log();
@@ -0,0 +1,105 @@
+extern crate faster_rs;
+
+use faster_rs::{FasterError, FasterKv, FasterKvBuilder, RawBytes, Status};
+use local_channel::mpsc::Receiver;
+
+fn raw_store() -> FasterKv<RawBytes> {
+    FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024)
+        .build_with_codec::<RawBytes>()
+        .unwrap()
+}
+
+#[monoio::test]
+async fn raw_bytes_roundtrip() {
+    let store = raw_store();
+    let session = store.session();
+    let key = b"key".to_vec();
+    let value = vec![0u8, 1, 2, 3];
+
+    let upsert = session.upsert(&key, &value).unwrap();
+    assert!(upsert == Status::Ok || upsert == Status::Pending);
+
+    let (res, mut recv): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+}
+
+#[monoio::test]
+async fn raw_bytes_accept_borrowed_slices() {
+    let store = raw_store();
+    let session = store.session();
+    let key: &[u8] = b"key";
+    let value: &[u8] = &[0u8, 1, 2, 3];
+
+    let upsert = session.upsert(key, value).unwrap();
+    assert!(upsert == Status::Ok || upsert == Status::Pending);
+
+    // Slices and vectors encode to the same bytes
+    let (res, mut recv): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(&key.to_vec()).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+
+    let (res, mut recv): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+
+    assert_eq!(session.delete(key).unwrap(), Status::Ok);
+    let (res, _): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(key).unwrap();
+    assert_eq!(res, Status::NotFound);
+}
+
+#[monoio::test]
+async fn raw_bytes_are_not_length_prefixed() {
+    let store = raw_store();
+    let session = store.session();
+    let key = b"key".to_vec();
+    let value = vec![7u8; 3];
+
+    session.upsert(&key, &value).unwrap();
+
+    // Appending with RMW only works if the stored bytes are exactly the value
+    session.rmw(&key, &vec![8u8]).unwrap();
+
+    let (res, mut recv): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), vec![7u8, 7, 7, 8]);
+}
+
+#[cfg(feature = "postcard")]
+#[monoio::test]
+async fn postcard_roundtrip() {
+    use faster_rs::Postcard;
+
+    let store = FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024)
+        .build_with_codec::<Postcard>()
+        .unwrap();
+    let session = store.session();
+    let key: u64 = 1;
+    let value = String::from("Hello, World!");
+
+    session.upsert(&key, &value).unwrap();
+
+    let (res, mut recv): (Status, Receiver<Result<String, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+}
+
+#[cfg(feature = "postcard")]
+#[monoio::test]
+async fn postcard_rmw_decodes_variable_width_numbers() {
+    use faster_rs::Postcard;
+
+    let store = FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024)
+        .build_with_codec::<Postcard>()
+        .unwrap();
+    let session = store.session();
+    let key: u64 = 1;
+
+    // Postcard encodes 100 in one byte and 300 in two, so the sum cannot be added on the bytes
+    session.upsert(&key, &(100u64)).unwrap();
+    session.rmw(&key, &(200u64)).unwrap();
+
+    let (res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+    assert_eq!(res, Status::Ok);
+    assert_eq!(recv.recv().await.unwrap().unwrap(), 300);
+}
@@ -0,0 +1,130 @@
+extern crate faster_rs;
+extern crate tempfile;
+
+use faster_rs::{FasterError, FasterKv, FasterKvBuilder, Status};
+use local_channel::mpsc::Receiver;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            let metadata = entry.metadata().unwrap();
+            match metadata.is_dir() {
+                true => dir_size(&entry.path()),
+                false => metadata.len(),
+            }
+        })
+        .sum()
+}
+
+#[test]
+fn log_addresses_are_ordered() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for key in 0..1000u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+
+    assert!(store.begin_address() <= store.head_address());
+    assert!(store.head_address() <= store.read_only_address());
+    assert!(store.read_only_address() <= store.tail_address());
+}
+
+#[test]
+fn compact_rejects_addresses_outside_the_log() {
+    let store = FasterKv::default();
+    let session = store.session();
+    session.upsert(&(1u64), &(1u64)).unwrap();
+
+    assert!(!store.compact(store.begin_address()));
+    assert!(!store.compact(store.tail_address() + 1));
+}
+
+#[monoio::test]
+async fn compaction_releases_disk_space() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path).with_segment_size(1 << 24);
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    // Overwrite a small set of keys until the log outgrows memory (128 MiB) by a few segments of 16 MiB
+    let num_keys: u64 = 1024;
+    let mut value = vec![0u8; 1024];
+    for round in 0..(3 << 16) / num_keys {
+        value[0] = round as u8;
+        for key in 0..num_keys {
+            session.upsert(&key, &value).unwrap();
+        }
+    }
+    session.complete_pending(true).unwrap();
+    let size_before = dir_size(tmp_dir.path());
+
+    let until = store.head_address();
+    assert!(store.compact(until));
+    assert_eq!(store.begin_address(), until);
+
+    // The log is truncated once the session has refreshed
+    let mut size_after = dir_size(tmp_dir.path());
+    for _ in 0..1000 {
+        if size_after < size_before {
+            break;
+        }
+        session.refresh();
+        session.complete_pending(true).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        size_after = dir_size(tmp_dir.path());
+    }
+    assert!(
+        size_after < size_before,
+        "Compaction should release disk space ({} >= {})",
+        size_after,
+        size_before
+    );
+
+    // Every key still holds its latest value
+    for key in 0..num_keys {
+        let (_, mut recv): (Status, Receiver<Result<Vec<u8>, FasterError>>) = session.read(&key).unwrap();
+        session.complete_pending(true).unwrap();
+        assert_eq!(recv.recv().await.unwrap().unwrap(), value);
+    }
+}
+
+#[monoio::test]
+async fn compaction_in_mutable_region_keeps_latest_values() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for key in 0..100u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+    for key in 0..10u64 {
+        session.delete(&key).unwrap();
+    }
+    for key in 10..20u64 {
+        session.upsert(&key, &(key + 1000)).unwrap();
+    }
+
+    // Every record is still in the mutable region
+    let until = store.tail_address();
+    assert!(until > store.read_only_address());
+    assert!(store.compact(until));
+    assert_eq!(store.begin_address(), until);
+
+    for key in 0..100u64 {
+        let (_, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+        session.complete_pending(true).unwrap();
+        let expected = match key {
+            0..=9 => None,
+            10..=19 => Some(key + 1000),
+            _ => Some(key),
+        };
+        assert_eq!(recv.recv().await.transpose().unwrap(), expected);
+    }
+}
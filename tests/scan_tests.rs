@@ -0,0 +1,233 @@
+extern crate faster_rs;
+extern crate tempfile;
+
+use faster_rs::{FasterKv, FasterKvBuilder, LogRecord, RawBytes};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+#[test]
+fn iter_yields_live_pairs() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for key in 0..100u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+    for key in 0..50u64 {
+        session.rmw(&key, &(1000u64)).unwrap();
+    }
+    for key in 90..100u64 {
+        session.delete(&key).unwrap();
+    }
+
+    let pairs: HashMap<u64, u64> = store.iter::<u64, u64>().map(|pair| pair.unwrap()).collect();
+    assert_eq!(pairs.len(), 90);
+    for key in 0..90u64 {
+        let expected = if key < 50 { key + 1000 } else { key };
+        assert_eq!(pairs[&key], expected);
+    }
+}
+
+#[test]
+fn iter_yields_each_key_once() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for round in 0..10u64 {
+        for key in 0..100u64 {
+            session.upsert(&key, &round).unwrap();
+        }
+    }
+
+    let pairs: Vec<(u64, u64)> = store.iter::<u64, u64>().map(|pair| pair.unwrap()).collect();
+    assert_eq!(pairs.len(), 100);
+    assert!(pairs.iter().all(|(_, value)| *value == 9));
+}
+
+#[test]
+fn iter_raw_returns_encoded_records() {
+    let store: FasterKv<RawBytes> = FasterKvBuilder::new(1 << 15, 1024 * 1024 * 1024)
+        .build_with_codec::<RawBytes>()
+        .unwrap();
+    let session = store.session();
+    session.upsert(&b"key".to_vec(), &b"value".to_vec()).unwrap();
+
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = store.iter_raw().collect();
+    assert_eq!(pairs, vec![(b"key".to_vec(), b"value".to_vec())]);
+}
+
+#[test]
+fn iter_starts_temporary_session() {
+    let store = FasterKv::default();
+    {
+        let session = store.session();
+        session.upsert(&(1u64), &(2u64)).unwrap();
+    }
+
+    let pairs: Vec<(u64, u64)> = store.iter::<u64, u64>().map(|pair| pair.unwrap()).collect();
+    assert_eq!(pairs, vec![(1, 2)]);
+}
+
+#[test]
+fn snapshot_iter_ignores_later_updates() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for key in 0..100u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+
+    let mut snapshot = store.snapshot_iter::<u64, u64>();
+    assert_eq!(snapshot.end_address(), store.tail_address());
+    for key in 0..100u64 {
+        session.upsert(&key, &(key + 1000)).unwrap();
+    }
+    session.upsert(&(100u64), &(100u64)).unwrap();
+
+    let first = snapshot.next().unwrap().unwrap();
+    let mut pairs: HashMap<u64, u64> = snapshot.map(|pair| pair.unwrap()).collect();
+    pairs.insert(first.0, first.1);
+    assert_eq!(pairs.len(), 100);
+    assert!(pairs.iter().all(|(key, value)| key == value));
+}
+
+#[test]
+fn iter_reads_records_from_disk() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    // More than fits into the in-memory log
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).unwrap();
+    }
+    session.complete_pending(true).unwrap();
+    assert!(store.head_address() > store.begin_address());
+
+    let mut count = 0;
+    for pair in store.iter::<u64, Vec<u8>>() {
+        let (_, read) = pair.unwrap();
+        assert_eq!(read, value);
+        count += 1;
+    }
+    assert_eq!(count, num_keys);
+}
+
+#[test]
+fn scan_range_yields_every_version() {
+    let store = FasterKv::default();
+    let session = store.session();
+    for key in 0..10u64 {
+        session.upsert(&key, &key).unwrap();
+    }
+    // Makes the log read-only, so that the following updates append new versions
+    drop(store.snapshot_iter_raw());
+    let resume_from = store.tail_address();
+    session.rmw(&(1u64), &(100u64)).unwrap();
+    session.delete(&(2u64)).unwrap();
+
+    let records: Vec<LogRecord<u64, u64>> = store
+        .scan_range(store.begin_address(), store.tail_address())
+        .map(|record| record.unwrap())
+        .collect();
+    assert_eq!(records.len(), 12);
+    for (record, next) in records.iter().zip(records.iter().skip(1)) {
+        assert_eq!(record.next_address, next.address);
+    }
+    assert_eq!(records[1].value, Some(1));
+    assert_eq!((records[10].key, records[10].value), (1, Some(101)));
+    assert_eq!((records[11].key, records[11].value), (2, None));
+
+    let changes: Vec<LogRecord<u64, u64>> = store
+        .scan_range(resume_from, store.tail_address())
+        .map(|record| record.unwrap())
+        .collect();
+    assert_eq!(changes, records[10..].to_vec());
+}
+
+// FASTER's log pages are 32 MiB, and records never span two pages
+const PAGE_BITS: u32 = 25;
+
+#[test]
+fn scan_range_addresses_span_pages() {
+    let store = FasterKv::default();
+    let session = store.session();
+
+    // Records of about 4 KiB do not fill a page exactly, so every page ends in padding
+    let value = vec![7u8; 4000];
+    let num_keys: u64 = 30_000;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).unwrap();
+    }
+
+    let tail = store.tail_address();
+    let records: Vec<(u64, u64, u64)> = store
+        .scan_range::<u64, Vec<u8>>(store.begin_address(), tail)
+        .map(|record| {
+            let record = record.unwrap();
+            (record.key, record.address, record.next_address)
+        })
+        .collect();
+    assert_eq!(records.len() as u64, num_keys);
+    assert!(records.last().unwrap().1 >> PAGE_BITS >= 2, "Records should span several pages");
+
+    let mut page_starts = 0;
+    for (i, (previous, record)) in records.iter().zip(records.iter().skip(1)).enumerate() {
+        assert_eq!(record.0, i as u64 + 1);
+        if record.1 != previous.2 {
+            // Only the padding at the end of a page is skipped
+            assert_eq!(record.1 % (1 << PAGE_BITS), 0);
+            assert_eq!(record.1 >> PAGE_BITS, (previous.1 >> PAGE_BITS) + 1);
+            page_starts += 1;
+        }
+        // A scan that continues behind a record starts with the record after it
+        if record.1 >> PAGE_BITS != previous.1 >> PAGE_BITS || i % 1000 == 0 {
+            let resumed = store.scan_range::<u64, Vec<u8>>(previous.2, tail).next().unwrap().unwrap();
+            assert_eq!((resumed.key, resumed.address, resumed.next_address), *record);
+        }
+    }
+    assert!(page_starts >= 2);
+}
+
+#[test]
+fn scan_range_resumes_from_disk() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.session();
+
+    // More than fits into the in-memory log
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).unwrap();
+    }
+    session.complete_pending(true).unwrap();
+
+    let tail = store.tail_address();
+    let first: Vec<LogRecord<u64, Vec<u8>>> = store
+        .scan_range(store.begin_address(), tail)
+        .take(1000)
+        .map(|record| record.unwrap())
+        .collect();
+    let middle = &first[500];
+    assert!(middle.address < store.head_address(), "The record should be on disk");
+
+    // Resumes both at a record and right behind the record before it
+    for from in [middle.address, first[499].next_address] {
+        let resumed: Vec<LogRecord<u64, Vec<u8>>> = store
+            .scan_range(from, tail)
+            .take(500)
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(resumed, first[500..].to_vec());
+    }
+}
@@ -0,0 +1,39 @@
+extern crate faster_rs;
+
+use faster_rs::FasterKv;
+use std::fs;
+
+// The resident set size of this process in bytes, read from /proc
+fn resident_bytes() -> u64 {
+    let statm = fs::read_to_string("/proc/self/statm").unwrap();
+    let resident_pages: u64 = statm.split_whitespace().nth(1).unwrap().parse().unwrap();
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    resident_pages * page_size
+}
+
+// The only test in this file, so that no other test allocates concurrently
+#[test]
+fn starting_sessions_does_not_leak() {
+    let store = FasterKv::default();
+    let session = store.session();
+    session.upsert(&(1u64), &(1u64)).unwrap();
+    drop(session);
+
+    // Warm up, so that allocations that are only made once are not counted
+    for _ in 0..10_000 {
+        drop(store.session());
+    }
+    let resident_before = resident_bytes();
+
+    // A leaked session id of 37 bytes would add up to more than 10 MiB
+    for _ in 0..300_000 {
+        drop(store.session());
+    }
+    let resident_after = resident_bytes();
+    assert!(
+        resident_after < resident_before + (4 << 20),
+        "Starting sessions should not leak memory ({} -> {} bytes)",
+        resident_before,
+        resident_after
+    );
+}
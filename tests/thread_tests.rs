@@ -1,6 +1,6 @@
 extern crate faster_rs;
 
-use faster_rs::FasterKv;
+use faster_rs::{FasterError, FasterKv, Status};
 use local_channel::mpsc::Receiver;
 use std::sync::Arc;
 use std::thread;
@@ -12,10 +12,10 @@ async fn multi_threaded_test() {
 
     let initial_value: u64 = 100;
     let modification: u64 = 30;
-    store.start_session();
+    let session = store.session();
 
     for key in 0..ops {
-        store.upsert(&(key as u64), &initial_value, key);
+        session.upsert(&(key as u64), &initial_value).unwrap();
     }
 
     let num_threads = 16;
@@ -23,18 +23,15 @@ async fn multi_threaded_test() {
     for _ in 0..num_threads {
         let store = Arc::clone(&store);
         threads.push(thread::spawn(move || {
-            // Register FASTER thread
-            let _session = store.start_session();
+            // Register FASTER thread, which is unregistered when the session is dropped
+            let session = store.session();
 
             for key in 0..ops {
-                store.rmw(&(key as u64), &modification, key);
+                session.rmw(&(key as u64), &modification).unwrap();
             }
 
             // Make sure everything is completed
-            store.complete_pending(true);
-
-            // Unregister Thread
-            store.stop_session();
+            session.complete_pending(true).unwrap();
         }))
     }
 
@@ -44,9 +41,8 @@ async fn multi_threaded_test() {
 
     for key in 0..ops {
         let expected_value = initial_value + (modification * num_threads);
-        let (_res, mut recv): (u8, Receiver<u64>) = store.read(&key, ops + key);
-        assert_eq!(recv.recv().await.unwrap(), expected_value);
+        let (_res, mut recv): (Status, Receiver<Result<u64, FasterError>>) = session.read(&key).unwrap();
+        assert_eq!(recv.recv().await.unwrap().unwrap(), expected_value);
     }
-    store.complete_pending(true);
-    store.stop_session();
+    session.complete_pending(true).unwrap();
 }
@@ -0,0 +1,39 @@
+#![cfg(feature = "tokio")]
+extern crate faster_rs;
+extern crate tempfile;
+
+use faster_rs::FasterKvBuilder;
+use std::fs;
+use tempfile::TempDir;
+
+// Whether the thread that times the backoff outside of tokio has been started
+fn backoff_thread_started() -> bool {
+    fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter_map(|task| fs::read_to_string(task.unwrap().path().join("comm")).ok())
+        .any(|name| name.trim() == "faster-backoff")
+}
+
+#[tokio::test]
+async fn async_session_is_timed_by_tokio() {
+    let table_size: u64 = 1 << 14;
+    let log_size: u64 = 1 << 27;
+    let tmp_dir = TempDir::new().unwrap();
+    let dir_path = tmp_dir.path().to_string_lossy().into_owned();
+    let mut builder = FasterKvBuilder::new(table_size, log_size);
+    builder.with_disk(&dir_path);
+    let store = builder.build().unwrap();
+    let session = store.async_session();
+
+    // Write more than fits into the in-memory log so that the first records are read from disk
+    let value = vec![7u8; 1024];
+    let num_keys: u64 = 1 << 18;
+    for key in 0..num_keys {
+        session.upsert(&key, &value).await.unwrap();
+    }
+    for key in 0..16 {
+        let read: Option<Vec<u8>> = session.read(&key).await.unwrap();
+        assert_eq!(read, Some(value.clone()));
+    }
+    assert!(!backoff_thread_started());
+}